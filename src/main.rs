@@ -1,4 +1,5 @@
 use std::{env, error::Error};
+mod random;
 mod task_01;
 mod task_02;
 mod task_03;
@@ -0,0 +1,9 @@
+//! A small deterministic generator for benchmarks and generated test inputs.
+
+/// Advances `state` and returns the next pseudo random number below `bound`.
+pub fn next_random(state: &mut u64, bound: u64) -> u64 {
+    *state = state
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+    (*state >> 33) % bound
+}
@@ -1,4 +1,4 @@
-use std::{error::Error, fs};
+use std::{cmp::Ordering, error::Error, fs};
 
 type N = i32;
 type Input = Vec<Vec<N>>;
@@ -17,47 +17,124 @@ fn read_input(path: String) -> Result<Input, Box<dyn Error>> {
         .collect())
 }
 
-#[derive(PartialEq, Debug)]
+/// Describes which reports count as safe.
+///
+/// Neighbouring levels must differ by `min_delta..=max_delta` in one direction.
+/// Equal neighbours are rejected under `strict` monotonicity and accepted without it,
+/// whatever the range says. A range with `min_delta > max_delta` rejects every other step.
+/// Up to `max_removals` levels may be dropped to make a report safe.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SafetyPolicy {
+    min_delta: N,
+    max_delta: N,
+    strict: bool,
+    max_removals: usize,
+}
+
+const FIRST_POLICY: SafetyPolicy = SafetyPolicy {
+    min_delta: 1,
+    max_delta: 3,
+    strict: true,
+    max_removals: 0,
+};
+
+const SECOND_POLICY: SafetyPolicy = SafetyPolicy {
+    max_removals: 1,
+    ..FIRST_POLICY
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 enum Direction {
     Unknown,
     Increments,
     Decrements,
 }
 
-fn report_is_safe(report: Vec<N>) -> bool {
-    let firsts = report.iter();
-    let seconds = report[1..].iter();
+/// How one level relates to the level before it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Step {
+    Rise(N),
+    Fall(N),
+    Flat,
+}
 
-    let mut direction: Direction = Direction::Unknown;
+/// States of the report checker.
+///
+/// `Level` remembers the last level and the direction established so far.
+/// The direction stays `Unknown` only while every step has been `Flat`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    Empty,
+    Level { last: N, direction: Direction },
+    Unsafe,
+}
 
-    for (a, b) in firsts.zip(seconds) {
-        match direction {
-            Direction::Decrements => {
-                if a < b {
-                    return false;
-                }
-            }
-            Direction::Increments => {
-                if a > b {
-                    return false;
-                }
-            }
-            Direction::Unknown => {
-                if a > b {
-                    direction = Direction::Decrements;
-                } else if b > a {
-                    direction = Direction::Increments
-                }
+fn delta_is_safe(policy: &SafetyPolicy, delta: N) -> bool {
+    match delta {
+        0 => !policy.strict,
+        delta => (policy.min_delta..=policy.max_delta).contains(&delta),
+    }
+}
+
+fn classify_step(a: N, b: N) -> Step {
+    match b.cmp(&a) {
+        Ordering::Greater => Step::Rise(b - a),
+        Ordering::Less => Step::Fall(a - b),
+        Ordering::Equal => Step::Flat,
+    }
+}
+
+fn step_delta(step: &Step) -> N {
+    match step {
+        Step::Rise(delta) | Step::Fall(delta) => *delta,
+        Step::Flat => 0,
+    }
+}
+
+/// Direction after taking `step`, or `None` if the step breaks monotonicity.
+fn next_direction(direction: &Direction, step: &Step) -> Option<Direction> {
+    match (direction, step) {
+        (direction, Step::Flat) => Some(*direction),
+        (Direction::Unknown, Step::Rise(_)) => Some(Direction::Increments),
+        (Direction::Unknown, Step::Fall(_)) => Some(Direction::Decrements),
+        (Direction::Increments, Step::Rise(_)) => Some(Direction::Increments),
+        (Direction::Decrements, Step::Fall(_)) => Some(Direction::Decrements),
+        (Direction::Increments, Step::Fall(_)) => None,
+        (Direction::Decrements, Step::Rise(_)) => None,
+    }
+}
+
+fn next_state(policy: &SafetyPolicy, state: &State, level: N) -> State {
+    match state {
+        State::Empty => State::Level {
+            last: level,
+            direction: Direction::Unknown,
+        },
+        State::Unsafe => State::Unsafe,
+        State::Level { last, direction } => {
+            let step = classify_step(*last, level);
+
+            if !delta_is_safe(policy, step_delta(&step)) {
+                return State::Unsafe;
             }
-        }
 
-        let delta = (a - b).abs();
-        if delta < 1 || delta > 3 {
-            return false;
+            match next_direction(direction, &step) {
+                Some(direction) => State::Level {
+                    last: level,
+                    direction,
+                },
+                None => State::Unsafe,
+            }
         }
     }
+}
 
-    return true;
+fn report_is_safe(policy: &SafetyPolicy, report: &[N]) -> bool {
+    let state = report.iter().fold(State::Empty, |state, level| {
+        next_state(policy, &state, *level)
+    });
+
+    state != State::Unsafe
 }
 
 fn solution_1(path: String) -> Result<usize, Box<dyn Error>> {
@@ -65,7 +142,7 @@ fn solution_1(path: String) -> Result<usize, Box<dyn Error>> {
 
     Ok(input
         .into_iter()
-        .filter(|r| report_is_safe(r.to_owned()))
+        .filter(|r| report_is_safe(&FIRST_POLICY, r))
         .count())
 }
 
@@ -75,6 +152,106 @@ fn first() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+fn step_is_safe(policy: &SafetyPolicy, direction: &Direction, a: N, b: N) -> bool {
+    let state = State::Level {
+        last: a,
+        direction: *direction,
+    };
+
+    next_state(policy, &state, b) != State::Unsafe
+}
+
+fn report_is_dampened_safe_in_direction(
+    policy: &SafetyPolicy,
+    report: &[N],
+    direction: &Direction,
+) -> bool {
+    let n = report.len();
+    if n <= 2 {
+        return true;
+    }
+
+    // prefix_safe[i]: report[..=i] is safe, suffix_safe[i]: report[i..] is safe.
+    let mut prefix_safe = vec![true; n];
+    for i in 1..n {
+        prefix_safe[i] =
+            prefix_safe[i - 1] && step_is_safe(policy, direction, report[i - 1], report[i]);
+    }
+
+    let mut suffix_safe = vec![true; n];
+    for i in (0..n - 1).rev() {
+        suffix_safe[i] =
+            suffix_safe[i + 1] && step_is_safe(policy, direction, report[i], report[i + 1]);
+    }
+
+    if prefix_safe[n - 1] {
+        return true;
+    }
+
+    (0..n).any(|removed| -> bool {
+        if removed == 0 {
+            return suffix_safe[1];
+        }
+        if removed == n - 1 {
+            return prefix_safe[n - 2];
+        }
+
+        prefix_safe[removed - 1]
+            && suffix_safe[removed + 1]
+            && step_is_safe(policy, direction, report[removed - 1], report[removed + 1])
+    })
+}
+
+/// Decides in O(n) whether a report is safe after removing at most one level.
+fn report_is_dampened_safe(policy: &SafetyPolicy, report: &[N]) -> bool {
+    report_is_dampened_safe_in_direction(policy, report, &Direction::Increments)
+        || report_is_dampened_safe_in_direction(policy, report, &Direction::Decrements)
+}
+
+/// Fewest removals that leave a safe report in the given direction.
+///
+/// `removals[j]` is the fewest removals before `j` such that `report[j]` is kept
+/// and everything kept up to it is safe. Only kept predecessors at most
+/// `max_removals + 1` positions back can contribute, which makes this O(n·k).
+fn fewest_removals_in_direction(
+    policy: &SafetyPolicy,
+    report: &[N],
+    direction: &Direction,
+) -> usize {
+    let n = report.len();
+    let window = policy.max_removals + 1;
+
+    let mut removals: Vec<usize> = Vec::with_capacity(n);
+    for j in 0..n {
+        let best = (j.saturating_sub(window)..j)
+            .filter(|i| step_is_safe(policy, direction, report[*i], report[j]))
+            .map(|i| removals[i] + (j - i - 1))
+            .fold(j, usize::min);
+
+        removals.push(best);
+    }
+
+    removals
+        .iter()
+        .enumerate()
+        .map(|(j, removed)| removed + (n - 1 - j))
+        .fold(n.saturating_sub(1), usize::min)
+}
+
+/// Decides whether a report is safe after removing at most `policy.max_removals` levels.
+fn report_is_safe_with_removals(policy: &SafetyPolicy, report: &[N]) -> bool {
+    match policy.max_removals {
+        0 => report.is_empty() || report_is_safe(policy, report),
+        1 => report_is_dampened_safe(policy, report),
+        _ => [Direction::Increments, Direction::Decrements]
+            .iter()
+            .any(|direction| {
+                fewest_removals_in_direction(policy, report, direction) <= policy.max_removals
+            }),
+    }
+}
+
+#[cfg(test)]
 fn get_subreports(report: Vec<N>) -> Input {
     let mut subreports: Input = Vec::new();
 
@@ -89,22 +266,40 @@ fn get_subreports(report: Vec<N>) -> Input {
         subreports.push(subreport);
     }
 
-    return subreports;
+    subreports
+}
+
+/// Reference implementation for `report_is_safe_with_removals`, exponential in `max_removals`.
+#[cfg(test)]
+fn report_is_safe_brute_force(policy: &SafetyPolicy, report: &[N]) -> bool {
+    if report.len() <= 1 || report_is_safe(policy, report) {
+        return true;
+    }
+    if policy.max_removals == 0 {
+        return false;
+    }
+
+    let policy = SafetyPolicy {
+        max_removals: policy.max_removals - 1,
+        ..*policy
+    };
+
+    get_subreports(report.to_owned())
+        .iter()
+        .any(|report| report_is_safe_brute_force(&policy, report))
+}
+
+fn count_safe_reports(policy: &SafetyPolicy, input: &Input) -> usize {
+    input
+        .iter()
+        .filter(|report| report_is_safe_with_removals(policy, report))
+        .count()
 }
 
 fn solution_2(path: String) -> Result<usize, Box<dyn Error>> {
     let input = read_input(path)?;
 
-    Ok(input
-        .into_iter()
-        .filter(|report| -> bool {
-            let subreports = get_subreports(report.to_owned());
-
-            subreports
-                .iter()
-                .any(|report| report_is_safe(report.to_owned()))
-        })
-        .count())
+    Ok(count_safe_reports(&SECOND_POLICY, &input))
 }
 
 fn second() -> Result<(), Box<dyn Error>> {
@@ -122,7 +317,32 @@ pub fn main() -> Result<(), Box<dyn Error>> {
 
 #[cfg(test)]
 mod tests {
-    use super::{solution_1, solution_2};
+    use super::{
+        classify_step, count_safe_reports, next_direction, next_state, read_input,
+        report_is_dampened_safe, report_is_safe, report_is_safe_brute_force,
+        report_is_safe_with_removals, solution_1, solution_2, Direction, SafetyPolicy, State, Step,
+        FIRST_POLICY, N, SECOND_POLICY,
+    };
+    use crate::random::next_random;
+
+    fn generate_reports(seed: u64, count: usize, max_length: usize) -> Vec<Vec<N>> {
+        let mut state = seed;
+        let mut next = move |bound| next_random(&mut state, bound);
+
+        (0..count)
+            .map(|_| -> Vec<N> {
+                let length = 2 + next(max_length as u64 - 1) as usize;
+                let mut level = next(20) as N;
+
+                (0..length)
+                    .map(|_| -> N {
+                        level += next(9) as N - 4;
+                        level
+                    })
+                    .collect()
+            })
+            .collect()
+    }
 
     #[test]
     fn solves_example_1_as_expected() {
@@ -139,4 +359,309 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn dampener_matches_brute_force_on_example() {
+        let input = read_input("./inputs/02/example.txt".to_owned()).unwrap();
+
+        for report in input {
+            assert_eq!(
+                report_is_dampened_safe(&SECOND_POLICY, &report),
+                report_is_safe_brute_force(&SECOND_POLICY, &report),
+                "{:?}",
+                report
+            );
+        }
+    }
+
+    #[test]
+    fn dampener_matches_brute_force_on_generated_reports() {
+        for report in generate_reports(42, 5000, 10) {
+            assert_eq!(
+                report_is_dampened_safe(&SECOND_POLICY, &report),
+                report_is_safe_brute_force(&SECOND_POLICY, &report),
+                "{:?}",
+                report
+            );
+        }
+    }
+
+    #[test]
+    fn dampener_handles_long_reports() {
+        let mut report = (0..100_000).collect::<Vec<N>>();
+        assert!(report_is_dampened_safe(&SECOND_POLICY, &report));
+
+        report[50_000] = -7;
+        assert!(report_is_dampened_safe(&SECOND_POLICY, &report));
+
+        report[70_000] = -7;
+        assert!(!report_is_dampened_safe(&SECOND_POLICY, &report));
+    }
+
+    #[test]
+    fn presets_agree_with_solutions() {
+        let input = read_input("./inputs/02/example.txt".to_owned()).unwrap();
+
+        assert_eq!(count_safe_reports(&FIRST_POLICY, &input), 2);
+        assert_eq!(count_safe_reports(&SECOND_POLICY, &input), 4);
+    }
+
+    #[test]
+    fn removals_match_brute_force_on_generated_reports() {
+        let policies = [
+            SafetyPolicy {
+                max_removals: 2,
+                ..FIRST_POLICY
+            },
+            SafetyPolicy {
+                max_removals: 3,
+                ..FIRST_POLICY
+            },
+            SafetyPolicy {
+                min_delta: 2,
+                max_delta: 4,
+                strict: false,
+                max_removals: 2,
+            },
+            SafetyPolicy {
+                strict: false,
+                ..SECOND_POLICY
+            },
+        ];
+
+        for policy in policies.iter() {
+            for report in generate_reports(7, 2000, 8) {
+                assert_eq!(
+                    report_is_safe_with_removals(policy, &report),
+                    report_is_safe_brute_force(policy, &report),
+                    "{:?} {:?}",
+                    policy,
+                    report
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn non_strict_policy_accepts_plateaus() {
+        let report = Vec::from([1, 2, 2, 4, 5]);
+        let policy = SafetyPolicy {
+            strict: false,
+            ..FIRST_POLICY
+        };
+
+        assert!(!report_is_safe_with_removals(&FIRST_POLICY, &report));
+        assert!(report_is_safe_with_removals(&policy, &report));
+    }
+
+    #[test]
+    fn strict_policy_rejects_plateaus_whatever_the_range() {
+        let report = Vec::from([1, 2, 2, 4, 5]);
+        let from_zero = SafetyPolicy {
+            min_delta: 0,
+            ..FIRST_POLICY
+        };
+        let lenient_from_two = SafetyPolicy {
+            min_delta: 2,
+            strict: false,
+            ..FIRST_POLICY
+        };
+
+        assert!(!report_is_safe(&from_zero, &report));
+        assert!(report_is_safe(&from_zero, &[1, 2, 4, 5]));
+        assert!(report_is_safe(&lenient_from_two, &[1, 1, 3, 5]));
+        assert!(!report_is_safe(&lenient_from_two, &[1, 1, 2, 4]));
+    }
+
+    #[test]
+    fn tolerates_up_to_k_removals() {
+        let report = Vec::from([1, 2, 9, 3, 9, 4, 5]);
+        let policy = |max_removals| SafetyPolicy {
+            max_removals,
+            ..FIRST_POLICY
+        };
+
+        assert!(!report_is_safe_with_removals(&policy(1), &report));
+        assert!(report_is_safe_with_removals(&policy(2), &report));
+        assert!(report_is_safe_with_removals(&policy(3), &report));
+    }
+
+    #[test]
+    fn classifies_steps() {
+        assert_eq!(classify_step(3, 5), Step::Rise(2));
+        assert_eq!(classify_step(5, 3), Step::Fall(2));
+        assert_eq!(classify_step(4, 4), Step::Flat);
+    }
+
+    #[test]
+    fn next_direction_covers_every_transition() {
+        let cases = [
+            (
+                Direction::Unknown,
+                Step::Rise(1),
+                Some(Direction::Increments),
+            ),
+            (
+                Direction::Unknown,
+                Step::Fall(1),
+                Some(Direction::Decrements),
+            ),
+            (Direction::Unknown, Step::Flat, Some(Direction::Unknown)),
+            (
+                Direction::Increments,
+                Step::Rise(1),
+                Some(Direction::Increments),
+            ),
+            (Direction::Increments, Step::Fall(1), None),
+            (
+                Direction::Increments,
+                Step::Flat,
+                Some(Direction::Increments),
+            ),
+            (Direction::Decrements, Step::Rise(1), None),
+            (
+                Direction::Decrements,
+                Step::Fall(1),
+                Some(Direction::Decrements),
+            ),
+            (
+                Direction::Decrements,
+                Step::Flat,
+                Some(Direction::Decrements),
+            ),
+        ];
+
+        for (direction, step, expected) in cases {
+            assert_eq!(
+                next_direction(&direction, &step),
+                expected,
+                "{:?} {:?}",
+                direction,
+                step
+            );
+        }
+    }
+
+    #[test]
+    fn next_state_covers_every_transition() {
+        let lenient = SafetyPolicy {
+            strict: false,
+            ..FIRST_POLICY
+        };
+        let level = |last, direction| State::Level { last, direction };
+
+        let cases = [
+            // Entering and leaving the terminal states.
+            (FIRST_POLICY, State::Empty, 5, level(5, Direction::Unknown)),
+            (FIRST_POLICY, State::Unsafe, 5, State::Unsafe),
+            // Unknown direction.
+            (
+                FIRST_POLICY,
+                level(5, Direction::Unknown),
+                7,
+                level(7, Direction::Increments),
+            ),
+            (
+                FIRST_POLICY,
+                level(5, Direction::Unknown),
+                3,
+                level(3, Direction::Decrements),
+            ),
+            (FIRST_POLICY, level(5, Direction::Unknown), 5, State::Unsafe),
+            (
+                lenient,
+                level(5, Direction::Unknown),
+                5,
+                level(5, Direction::Unknown),
+            ),
+            (FIRST_POLICY, level(5, Direction::Unknown), 9, State::Unsafe),
+            (FIRST_POLICY, level(5, Direction::Unknown), 1, State::Unsafe),
+            // Increments.
+            (
+                FIRST_POLICY,
+                level(5, Direction::Increments),
+                8,
+                level(8, Direction::Increments),
+            ),
+            (
+                FIRST_POLICY,
+                level(5, Direction::Increments),
+                9,
+                State::Unsafe,
+            ),
+            (
+                FIRST_POLICY,
+                level(5, Direction::Increments),
+                4,
+                State::Unsafe,
+            ),
+            (
+                FIRST_POLICY,
+                level(5, Direction::Increments),
+                5,
+                State::Unsafe,
+            ),
+            (
+                lenient,
+                level(5, Direction::Increments),
+                5,
+                level(5, Direction::Increments),
+            ),
+            // Decrements.
+            (
+                FIRST_POLICY,
+                level(5, Direction::Decrements),
+                2,
+                level(2, Direction::Decrements),
+            ),
+            (
+                FIRST_POLICY,
+                level(5, Direction::Decrements),
+                1,
+                State::Unsafe,
+            ),
+            (
+                FIRST_POLICY,
+                level(5, Direction::Decrements),
+                6,
+                State::Unsafe,
+            ),
+            (
+                FIRST_POLICY,
+                level(5, Direction::Decrements),
+                5,
+                State::Unsafe,
+            ),
+            (
+                lenient,
+                level(5, Direction::Decrements),
+                5,
+                level(5, Direction::Decrements),
+            ),
+        ];
+
+        for (policy, state, next_level, expected) in cases {
+            assert_eq!(
+                next_state(&policy, &state, next_level),
+                expected,
+                "{:?} {:?} {}",
+                policy,
+                state,
+                next_level
+            );
+        }
+    }
+
+    #[test]
+    fn equal_first_levels_do_not_poison_direction() {
+        let lenient = SafetyPolicy {
+            strict: false,
+            ..FIRST_POLICY
+        };
+
+        assert!(!report_is_safe(&FIRST_POLICY, &[4, 4, 5, 6]));
+        assert!(report_is_safe(&lenient, &[4, 4, 5, 6]));
+        assert!(report_is_safe(&lenient, &[4, 4, 3, 1]));
+        assert!(!report_is_safe(&lenient, &[4, 4, 5, 4]));
+    }
 }
@@ -1,45 +1,88 @@
-use std::{error::Error, fs};
-
-use regex::Regex;
+use std::{
+    env,
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io::{self, Read},
+};
 
 fn read_input(path: String) -> Result<String, Box<dyn Error>> {
     Ok(fs::read_to_string(path)?)
 }
 
 type N = i32;
+/// Products and their sums, wide enough for any product of two operands.
+type Sum = i64;
 
-#[derive(Debug, PartialEq)]
-struct Mul {
-    a: N,
-    b: N,
+/// Byte range `start..end` of something found in the input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Span {
+    start: usize,
+    end: usize,
 }
 
-fn match_muls(input: String) -> Vec<Mul> {
-    let mul_regex = Regex::new(r"mul\((?<a>\d+),(?<b>\d+)\)").unwrap();
-
-    let captures = mul_regex.captures_iter(&input);
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TokenKind<'a> {
+    /// A run of letters and apostrophes, like `don't` or `xmul`.
+    Word(&'a str),
+    /// A run of ASCII digits.
+    Number(&'a str),
+    OpenParen,
+    Comma,
+    CloseParen,
+    /// Any other character, which can never be part of an instruction.
+    Junk,
+}
 
-    captures
-        .filter_map(|capture| -> Option<Mul> {
-            let a = capture.name("a")?.as_str().parse::<N>().ok()?;
-            let b = capture.name("b")?.as_str().parse::<N>().ok()?;
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Token<'a> {
+    kind: TokenKind<'a>,
+    span: Span,
+}
 
-            Some(Mul { a, b })
-        })
-        .collect()
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '\''
 }
 
-fn solution_1(path: String) -> Result<N, Box<dyn Error>> {
-    let input = read_input(path)?;
-    let muls = match_muls(input);
+fn lex(input: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+
+        let mut take_while = |predicate: fn(char) -> bool| -> usize {
+            while let Some((index, c)) = chars.next_if(|(_, c)| predicate(*c)) {
+                end = index + c.len_utf8();
+            }
+            end
+        };
+
+        let kind = match c {
+            '(' => TokenKind::OpenParen,
+            ',' => TokenKind::Comma,
+            ')' => TokenKind::CloseParen,
+            c if c.is_ascii_digit() => {
+                TokenKind::Number(&input[start..take_while(|c| c.is_ascii_digit())])
+            }
+            c if is_word_char(c) => TokenKind::Word(&input[start..take_while(is_word_char)]),
+            _ => TokenKind::Junk,
+        };
+
+        tokens.push(Token {
+            kind,
+            span: Span { start, end },
+        });
+    }
 
-    Ok(muls.iter().map(|Mul { a, b }| -> N { (*a) * (*b) }).sum())
+    tokens
 }
 
-fn first() -> Result<(), Box<dyn Error>> {
-    let wanted = solution_1("./inputs/03/input.txt".to_owned())?;
-    println!("{}", wanted);
-    Ok(())
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+struct Mul {
+    a: N,
+    b: N,
 }
 
 #[derive(Debug, PartialEq)]
@@ -49,63 +92,417 @@ enum Instruction {
     Mul { a: N, b: N },
 }
 
-fn match_instructions(input: String) -> Vec<Instruction> {
-    let instruction_regex =
-        Regex::new(r"((?<do>do\(\))|(?<dont>don't\(\))|(?<mul>mul\((?<a>\d+),(?<b>\d+)\)))")
-            .unwrap();
+/// Operands of the puzzle have one to three digits.
+const MAX_DIGITS: usize = 3;
+
+/// Names that `build_instruction` knows about.
+const INSTRUCTION_NAMES: [&str; 3] = ["do", "don't", "mul"];
+
+/// Builds the instruction called `name` with `args`, if there is one.
+///
+/// This is the single place that knows the arities of instructions.
+fn build_instruction(name: &str, args: &[N]) -> Option<Instruction> {
+    match (name, args) {
+        ("do", []) => Some(Instruction::Do),
+        ("don't", []) => Some(Instruction::Dont),
+        ("mul", [a, b]) => Some(Instruction::Mul { a: *a, b: *b }),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Spanned<T> {
+    value: T,
+    span: Span,
+}
+
+/// Why something that started like an instruction was not one.
+#[derive(Debug, PartialEq)]
+enum Rejection {
+    MissingOpenParen,
+    UnexpectedToken { found: String },
+    UnexpectedEnd,
+    TooManyDigits { digits: String, max_digits: usize },
+    Overflow { digits: String },
+    WrongArity { name: String, arity: usize },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::MissingOpenParen => write!(f, "expected `(` right after the name"),
+            Rejection::UnexpectedToken { found } => write!(f, "unexpected `{}`", found),
+            Rejection::UnexpectedEnd => write!(f, "input ended inside the instruction"),
+            Rejection::TooManyDigits { digits, max_digits } => write!(
+                f,
+                "operand `{}` has {} digits, at most {} are allowed",
+                digits,
+                digits.len(),
+                max_digits
+            ),
+            Rejection::Overflow { digits } => write!(f, "operand `{}` does not fit", digits),
+            Rejection::WrongArity { name, arity } => {
+                write!(f, "`{}` does not take {} operands", name, arity)
+            }
+        }
+    }
+}
+
+fn unexpected(input: &str, token: &Token) -> Rejection {
+    Rejection::UnexpectedToken {
+        found: input[token.span.start..token.span.end].to_owned(),
+    }
+}
+
+fn parse_operand(digits: &str, max_digits: usize) -> Result<N, Rejection> {
+    if digits.len() > max_digits {
+        return Err(Rejection::TooManyDigits {
+            digits: digits.to_owned(),
+            max_digits,
+        });
+    }
+
+    digits.parse::<N>().map_err(|_| Rejection::Overflow {
+        digits: digits.to_owned(),
+    })
+}
+
+/// Parses the `(a,b,…)` argument list starting at `tokens[0]`.
+///
+/// Returns the arguments and the index of the closing paren,
+/// or the rejection and the index of the token that caused it.
+fn parse_args(
+    input: &str,
+    tokens: &[Token],
+    max_digits: usize,
+) -> Result<(Vec<N>, usize), (Rejection, usize)> {
+    let mut args = Vec::new();
+    let mut index = 1;
+
+    match tokens.first() {
+        Some(Token {
+            kind: TokenKind::OpenParen,
+            ..
+        }) => {}
+        _ => return Err((Rejection::MissingOpenParen, 0)),
+    }
+
+    loop {
+        let Some(token) = tokens.get(index) else {
+            return Err((Rejection::UnexpectedEnd, index - 1));
+        };
+        match token.kind {
+            TokenKind::CloseParen if args.is_empty() => return Ok((args, index)),
+            TokenKind::Number(digits) => {
+                args.push(parse_operand(digits, max_digits).map_err(|reason| (reason, index))?)
+            }
+            _ => return Err((unexpected(input, token), index)),
+        }
+
+        let Some(token) = tokens.get(index + 1) else {
+            return Err((Rejection::UnexpectedEnd, index));
+        };
+        match token.kind {
+            TokenKind::Comma => index += 2,
+            TokenKind::CloseParen => return Ok((args, index + 1)),
+            _ => return Err((unexpected(input, token), index + 1)),
+        }
+    }
+}
+
+/// Longest tail of `word` that names an instruction, with its offset in `word`.
+fn instruction_name(word: &str) -> Option<(usize, &str)> {
+    word.char_indices()
+        .map(|(offset, _)| (offset, &word[offset..]))
+        .find(|(_, name)| INSTRUCTION_NAMES.contains(name))
+}
+
+/// A parsed instruction with the number of tokens it used, or why it was rejected.
+type Parsed = Result<(Spanned<Instruction>, usize), Spanned<Rejection>>;
+
+/// Tries to read an instruction whose name ends `tokens[0]`.
+///
+/// Like the original regex, the name may be the tail of a longer word,
+/// so `xmul(2,4)` still yields a `mul`.
+/// Returns `None` if `tokens[0]` doesn't end in an instruction name at all.
+fn parse_instruction(input: &str, tokens: &[Token], max_digits: usize) -> Option<Parsed> {
+    let Token {
+        kind: TokenKind::Word(word),
+        span,
+    } = tokens.first()?
+    else {
+        return None;
+    };
+    let (offset, name) = instruction_name(word)?;
+    let start = span.start + offset;
+
+    let result = match parse_args(input, &tokens[1..], max_digits) {
+        Ok((args, close)) => {
+            let span = Span {
+                start,
+                end: tokens[close + 1].span.end,
+            };
+
+            match build_instruction(name, &args) {
+                Some(value) => Ok((Spanned { value, span }, close + 2)),
+                None => Err(Spanned {
+                    value: Rejection::WrongArity {
+                        name: name.to_owned(),
+                        arity: args.len(),
+                    },
+                    span,
+                }),
+            }
+        }
+        Err((value, index)) => Err(Spanned {
+            value,
+            span: Span {
+                start,
+                end: tokens
+                    .get(index + 1)
+                    .map_or(span.end, |token| token.span.end),
+            },
+        }),
+    };
+
+    Some(result)
+}
+
+/// Parses instructions and collects the near-misses that were rejected on the way.
+fn parse_with_rejections(
+    input: &str,
+    tokens: &[Token],
+    max_digits: usize,
+) -> (Vec<Spanned<Instruction>>, Vec<Spanned<Rejection>>) {
+    let mut instructions = Vec::new();
+    let mut rejections = Vec::new();
+    let mut index = 0;
+
+    while index < tokens.len() {
+        match parse_instruction(input, &tokens[index..], max_digits) {
+            Some(Ok((instruction, consumed))) => {
+                instructions.push(instruction);
+                index += consumed;
+            }
+            Some(Err(rejection)) => {
+                rejections.push(rejection);
+                index += 1;
+            }
+            None => index += 1,
+        }
+    }
+
+    (instructions, rejections)
+}
+
+#[cfg(test)]
+fn parse_instructions(
+    input: &str,
+    tokens: &[Token],
+    max_digits: usize,
+) -> Vec<Spanned<Instruction>> {
+    parse_with_rejections(input, tokens, max_digits).0
+}
+
+/// State that instructions are executed against.
+trait Machine {
+    fn execute(&mut self, instruction: &Instruction);
+}
 
-    let captures = instruction_regex.captures_iter(&input);
+/// The number an instruction contributes to a sum, if any.
+fn evaluate(instruction: &Instruction) -> Option<Sum> {
+    match instruction {
+        Instruction::Do | Instruction::Dont => None,
+        Instruction::Mul { a, b } => Some(Sum::from(*a) * Sum::from(*b)),
+    }
+}
+
+/// Sums every value, ignoring `do()` and `don't()`.
+#[derive(Debug, Default, PartialEq)]
+struct Accumulator {
+    sum: Sum,
+}
 
-    captures
-        .filter_map(|capture| -> Option<Instruction> {
-            let i_do = capture.name("do");
-            let i_dont = capture.name("dont");
-            let i_mul = capture.name("mul");
+impl Machine for Accumulator {
+    fn execute(&mut self, instruction: &Instruction) {
+        self.sum += evaluate(instruction).unwrap_or(0);
+    }
+}
+
+/// Sums values only while enabled by `do()` and disabled by `don't()`.
+#[derive(Debug, PartialEq)]
+struct ConditionalAccumulator {
+    enabled: bool,
+    sum: Sum,
+}
 
-            match (i_do, i_dont, i_mul) {
-                (Some(_), _, _) => Some(Instruction::Do),
-                (None, Some(_), _) => Some(Instruction::Dont),
-                (None, None, _) => {
-                    let a = capture.name("a")?.as_str().parse::<N>().ok()?;
-                    let b = capture.name("b")?.as_str().parse::<N>().ok()?;
+impl Default for ConditionalAccumulator {
+    fn default() -> Self {
+        ConditionalAccumulator {
+            enabled: true,
+            sum: 0,
+        }
+    }
+}
 
-                    Some(Instruction::Mul { a, b })
+impl Machine for ConditionalAccumulator {
+    fn execute(&mut self, instruction: &Instruction) {
+        match instruction {
+            Instruction::Do => self.enabled = true,
+            Instruction::Dont => self.enabled = false,
+            instruction => {
+                if self.enabled {
+                    self.sum += evaluate(instruction).unwrap_or(0);
                 }
             }
+        }
+    }
+}
+
+fn run<'a, M: Machine>(
+    mut machine: M,
+    instructions: impl IntoIterator<Item = &'a Instruction>,
+) -> M {
+    for instruction in instructions {
+        machine.execute(instruction);
+    }
+
+    machine
+}
+
+#[cfg(test)]
+fn match_instructions(input: String) -> Vec<Instruction> {
+    parse_instructions(&input, &lex(&input), MAX_DIGITS)
+        .into_iter()
+        .map(|instruction| instruction.value)
+        .collect()
+}
+
+#[cfg(test)]
+fn match_muls(input: String) -> Vec<Mul> {
+    match_instructions(input)
+        .into_iter()
+        .filter_map(|instruction| -> Option<Mul> {
+            match instruction {
+                Instruction::Mul { a, b } => Some(Mul { a, b }),
+                _ => None,
+            }
         })
         .collect()
 }
 
+/// Bytes read from a stream at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Offset from which `input` may still change meaning once more input is appended.
+///
+/// That is the start of any instruction that ran into the end of `input`,
+/// and the tail of a trailing word that could still grow into an instruction name.
+fn unfinished_start(input: &str, tokens: &[Token], rejections: &[Spanned<Rejection>]) -> usize {
+    let longest_name = INSTRUCTION_NAMES
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(1);
+    let word_start = match tokens.last() {
+        Some(Token {
+            kind: TokenKind::Word(_),
+            span,
+        }) => span.start.max(input.len().saturating_sub(longest_name)),
+        _ => input.len(),
+    };
+
+    rejections
+        .iter()
+        .filter(|rejection| rejection.value == Rejection::UnexpectedEnd)
+        .map(|rejection| rejection.span.start)
+        .fold(word_start, usize::min)
+}
+
+/// Executes every instruction read from `reader` on `machine`, one chunk at a time.
+///
+/// Only the unfinished tail of a chunk is carried over to the next one,
+/// so memory stays bounded by `chunk_size` plus the longest unfinished instruction.
+fn scan<R: Read, M: Machine>(
+    mut reader: R,
+    mut machine: M,
+    chunk_size: usize,
+    max_digits: usize,
+) -> Result<M, io::Error> {
+    let mut chunk = vec![0; chunk_size];
+    let mut carry = String::new();
+
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+
+        carry.push_str(&String::from_utf8_lossy(&chunk[..read]));
+        let tokens = lex(&carry);
+        let (instructions, rejections) = parse_with_rejections(&carry, &tokens, max_digits);
+
+        let cut = match read {
+            0 => carry.len(),
+            _ => unfinished_start(&carry, &tokens, &rejections),
+        };
+
+        machine = run(
+            machine,
+            instructions
+                .iter()
+                .filter(|instruction| instruction.span.start < cut)
+                .map(|instruction| &instruction.value),
+        );
+
+        if read == 0 {
+            return Ok(machine);
+        }
+
+        carry = carry.split_off(cut);
+    }
+}
+
+fn solution_1(path: String) -> Result<Sum, Box<dyn Error>> {
+    let file = File::open(path)?;
+
+    Ok(scan(file, Accumulator::default(), CHUNK_SIZE, MAX_DIGITS)?.sum)
+}
+
+fn first() -> Result<(), Box<dyn Error>> {
+    let wanted = solution_1("./inputs/03/input.txt".to_owned())?;
+    println!("{}", wanted);
+    Ok(())
+}
+
+#[cfg(test)]
 fn filter_instructions(instructions: Vec<Instruction>) -> Vec<Mul> {
-    let mut do_enabled = true;
+    let mut machine = ConditionalAccumulator::default();
 
     instructions
         .iter()
         .filter_map(|instruction| -> Option<Mul> {
+            machine.execute(instruction);
+
             match instruction {
-                Instruction::Do => {
-                    do_enabled = true;
-                }
-                Instruction::Dont => {
-                    do_enabled = false;
-                }
-                Instruction::Mul { a, b } => {
-                    if do_enabled {
-                        return Some(Mul { a: *a, b: *b });
-                    }
-                }
+                Instruction::Mul { a, b } if machine.enabled => Some(Mul { a: *a, b: *b }),
+                _ => None,
             }
-
-            None
         })
         .collect()
 }
 
-fn solution_2(path: String) -> Result<N, Box<dyn Error>> {
-    let input = read_input(path)?;
-    let muls = filter_instructions(match_instructions(input));
+fn solution_2(path: String) -> Result<Sum, Box<dyn Error>> {
+    let file = File::open(path)?;
 
-    Ok(muls.iter().map(|Mul { a, b }| -> N { (*a) * (*b) }).sum())
+    Ok(scan(
+        file,
+        ConditionalAccumulator::default(),
+        CHUNK_SIZE,
+        MAX_DIGITS,
+    )?
+    .sum)
 }
 
 fn second() -> Result<(), Box<dyn Error>> {
@@ -114,7 +511,184 @@ fn second() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+fn diagnose(input: &str, max_digits: usize) -> Vec<Spanned<Rejection>> {
+    parse_with_rejections(input, &lex(input), max_digits).1
+}
+
+fn print_diagnostics() -> Result<(), Box<dyn Error>> {
+    let input = read_input("./inputs/03/input.txt".to_owned())?;
+
+    for Spanned { value, span } in diagnose(&input, MAX_DIGITS) {
+        println!(
+            "{}: `{}` {}",
+            span.start,
+            &input[span.start..span.end],
+            value
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Highlight {
+    Enabled,
+    Disabled,
+    Toggle,
+}
+
+/// How an instruction is highlighted and the enabled sum after executing it.
+#[derive(Debug, PartialEq)]
+struct Annotation {
+    highlight: Highlight,
+    total: Sum,
+}
+
+fn annotate(input: &str) -> Vec<Spanned<Annotation>> {
+    let (instructions, _) = parse_with_rejections(input, &lex(input), MAX_DIGITS);
+    let mut machine = ConditionalAccumulator::default();
+
+    instructions
+        .into_iter()
+        .map(|Spanned { value, span }| -> Spanned<Annotation> {
+            let highlight = match value {
+                Instruction::Do | Instruction::Dont => Highlight::Toggle,
+                _ if machine.enabled => Highlight::Enabled,
+                _ => Highlight::Disabled,
+            };
+            machine.execute(&value);
+
+            Spanned {
+                value: Annotation {
+                    highlight,
+                    total: machine.sum,
+                },
+                span,
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RenderFormat {
+    Ansi,
+    Html,
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_plain(text: &str, format: RenderFormat) -> String {
+    match format {
+        RenderFormat::Ansi => text.to_owned(),
+        RenderFormat::Html => escape_html(text),
+    }
+}
+
+fn render_highlight(text: &str, highlight: Highlight, format: RenderFormat) -> String {
+    match format {
+        RenderFormat::Ansi => {
+            let style = match highlight {
+                Highlight::Enabled => "32",
+                Highlight::Disabled => "90",
+                Highlight::Toggle => "1",
+            };
+
+            format!("\x1b[{}m{}\x1b[0m", style, text)
+        }
+        RenderFormat::Html => {
+            let class = match highlight {
+                Highlight::Enabled => "enabled",
+                Highlight::Disabled => "disabled",
+                Highlight::Toggle => "toggle",
+            };
+
+            format!("<span class=\"{}\">{}</span>", class, escape_html(text))
+        }
+    }
+}
+
+/// Reproduces `input` with every instruction highlighted.
+///
+/// Each line is followed by one row per instruction on it, giving its column,
+/// its text and the running total after it, aligned across the whole input.
+fn render(input: &str, format: RenderFormat) -> String {
+    let annotations = annotate(input);
+    let text_width = annotations
+        .iter()
+        .map(|a| a.span.end - a.span.start)
+        .max()
+        .unwrap_or(0);
+    let total_width = annotations
+        .iter()
+        .map(|a| a.value.total.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let column_width = input
+        .lines()
+        .map(|line| line.len().to_string().len())
+        .max()
+        .unwrap_or(0);
+
+    let mut annotations = annotations.iter().peekable();
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+
+    for line in input.split_inclusive('\n') {
+        let line_end = line_start + line.trim_end_matches('\n').len();
+        let mut rendered = String::new();
+        let mut rows = Vec::new();
+        let mut position = line_start;
+
+        while let Some(Spanned { value, span }) = annotations.next_if(|a| a.span.end <= line_end) {
+            let text = &input[span.start..span.end];
+            rendered.push_str(&render_plain(&input[position..span.start], format));
+            rendered.push_str(&render_highlight(text, value.highlight, format));
+            rows.push(format!(
+                "  {:>column_width$}: {} │ {:>total_width$}",
+                span.start - line_start + 1,
+                render_plain(&format!("{:<text_width$}", text), format),
+                value.total,
+            ));
+            position = span.end;
+        }
+        rendered.push_str(&render_plain(&input[position..line_end], format));
+
+        lines.push(rendered);
+        lines.extend(rows);
+        line_start += line.len();
+    }
+
+    match format {
+        RenderFormat::Ansi => lines.join("\n"),
+        RenderFormat::Html => format!(
+            "<style>.enabled {{ color: green; }} .disabled {{ color: grey; }} .toggle {{ font-weight: bold; }}</style>\n<pre>\n{}\n</pre>",
+            lines.join("\n")
+        ),
+    }
+}
+
+fn print_render(format: RenderFormat) -> Result<(), Box<dyn Error>> {
+    let input = read_input("./inputs/03/input.txt".to_owned())?;
+    println!("{}", render(&input, format));
+    Ok(())
+}
+
 pub fn main() -> Result<(), Box<dyn Error>> {
+    if env::args().any(|arg| arg == "--diagnostics") {
+        return print_diagnostics();
+    }
+    if env::args().any(|arg| arg == "--render") {
+        return print_render(RenderFormat::Ansi);
+    }
+    if env::args().any(|arg| arg == "--render=html") {
+        return print_render(RenderFormat::Html);
+    }
+
     println!("01-1:");
     first()?;
     println!("01-2:");
@@ -124,8 +698,10 @@ pub fn main() -> Result<(), Box<dyn Error>> {
 #[cfg(test)]
 mod tests {
     use super::{
-        filter_instructions, match_instructions, match_muls, read_input, solution_1, solution_2,
-        Instruction, Mul, N,
+        annotate, diagnose, filter_instructions, lex, match_instructions, match_muls,
+        parse_instructions, read_input, render, run, scan, solution_1, solution_2, Accumulator,
+        Annotation, ConditionalAccumulator, Highlight, Instruction, Mul, Rejection, RenderFormat,
+        Span, Spanned, Sum, Token, TokenKind, MAX_DIGITS,
     };
 
     #[test]
@@ -146,7 +722,7 @@ mod tests {
     #[test]
     fn calculates_first_example() {
         let actual = solution_1("./inputs/03/example.txt".to_owned()).unwrap();
-        let expected: N = 161;
+        let expected: Sum = 161;
 
         assert_eq!(actual, expected);
     }
@@ -181,8 +757,326 @@ mod tests {
     #[test]
     fn calculates_second_example() {
         let actual = solution_2("./inputs/03/example2.txt".to_owned()).unwrap();
-        let expected: N = 48;
+        let expected: Sum = 48;
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn lexes_spanned_tokens() {
+        let actual = lex("xmul(2,45)");
+
+        let expected = Vec::from([
+            Token {
+                kind: TokenKind::Word("xmul"),
+                span: Span { start: 0, end: 4 },
+            },
+            Token {
+                kind: TokenKind::OpenParen,
+                span: Span { start: 4, end: 5 },
+            },
+            Token {
+                kind: TokenKind::Number("2"),
+                span: Span { start: 5, end: 6 },
+            },
+            Token {
+                kind: TokenKind::Comma,
+                span: Span { start: 6, end: 7 },
+            },
+            Token {
+                kind: TokenKind::Number("45"),
+                span: Span { start: 7, end: 9 },
+            },
+            Token {
+                kind: TokenKind::CloseParen,
+                span: Span { start: 9, end: 10 },
+            },
+        ]);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn parses_spanned_instructions() {
+        let actual = {
+            let input = "xmul(2,4)&don't()_mul(5,mul(3,7))undo()";
+            parse_instructions(input, &lex(input), MAX_DIGITS)
+        };
+
+        let expected = Vec::from([
+            Spanned {
+                value: Instruction::Mul { a: 2, b: 4 },
+                span: Span { start: 1, end: 9 },
+            },
+            Spanned {
+                value: Instruction::Dont,
+                span: Span { start: 10, end: 17 },
+            },
+            Spanned {
+                value: Instruction::Mul { a: 3, b: 7 },
+                span: Span { start: 24, end: 32 },
+            },
+            Spanned {
+                value: Instruction::Do,
+                span: Span { start: 35, end: 39 },
+            },
+        ]);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn rejects_malformed_instructions() {
+        for input in [
+            "mul(2,4",
+            "mul(2,)",
+            "mul(,4)",
+            "mul 2,4)",
+            "mul(2)",
+            "mul(2,4,6)",
+            "do(1)",
+        ] {
+            assert_eq!(
+                parse_instructions(input, &lex(input), MAX_DIGITS),
+                Vec::new(),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn machines_sum_instructions() {
+        let input = read_input("./inputs/03/example2.txt".to_owned()).unwrap();
+        let instructions = match_instructions(input);
+
+        assert_eq!(run(Accumulator::default(), &instructions).sum, 161);
+        assert_eq!(
+            run(ConditionalAccumulator::default(), &instructions),
+            ConditionalAccumulator {
+                enabled: true,
+                sum: 48
+            }
+        );
+    }
+
+    #[test]
+    fn enforces_digit_limit() {
+        let input = "mul(123,4)mul(1234,5)mul(12,345)";
+
+        let actual = match_instructions(input.to_owned());
+        let expected = Vec::from([
+            Instruction::Mul { a: 123, b: 4 },
+            Instruction::Mul { a: 12, b: 345 },
+        ]);
+        assert_eq!(actual, expected);
+
+        let actual = parse_instructions(input, &lex(input), 4)
+            .into_iter()
+            .map(|instruction| instruction.value)
+            .collect::<Vec<_>>();
+        let expected = Vec::from([
+            Instruction::Mul { a: 123, b: 4 },
+            Instruction::Mul { a: 1234, b: 5 },
+            Instruction::Mul { a: 12, b: 345 },
+        ]);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn multiplies_wide_operands_without_overflowing() {
+        let input = "mul(99999,99999)do()mul(12345,6789)";
+
+        let actual = scan(input.as_bytes(), Accumulator::default(), 8, 5).unwrap();
+        let expected = 99999 * 99999 + 12345 * 6789;
+        assert_eq!(actual.sum, expected);
+
+        let actual = scan(input.as_bytes(), Accumulator::default(), 8, MAX_DIGITS).unwrap();
+        assert_eq!(actual.sum, 0);
+    }
+
+    #[test]
+    fn reports_near_misses() {
+        let input = "mul(4*mul ( 2 , 4 )mul(1234,5)mul(99999999999,1)mul(1,2,3)mul(2,4)do";
+
+        let actual = diagnose(input, 11)
+            .into_iter()
+            .map(|rejection| (rejection.span.start, rejection.value))
+            .collect::<Vec<_>>();
+
+        let expected = Vec::from([
+            (
+                0,
+                Rejection::UnexpectedToken {
+                    found: "*".to_owned(),
+                },
+            ),
+            (6, Rejection::MissingOpenParen),
+            (
+                30,
+                Rejection::Overflow {
+                    digits: "99999999999".to_owned(),
+                },
+            ),
+            (
+                48,
+                Rejection::WrongArity {
+                    name: "mul".to_owned(),
+                    arity: 3,
+                },
+            ),
+            (66, Rejection::MissingOpenParen),
+        ]);
+        assert_eq!(actual, expected);
+
+        let actual = diagnose(input, MAX_DIGITS)
+            .into_iter()
+            .map(|rejection| (rejection.span.start, rejection.value.to_string()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            actual[2],
+            (
+                19,
+                "operand `1234` has 4 digits, at most 3 are allowed".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn scans_across_chunk_boundaries() {
+        for path in ["./inputs/03/example.txt", "./inputs/03/example2.txt"] {
+            let input = read_input(path.to_owned()).unwrap();
+
+            for chunk_size in 1..=input.len() {
+                let first = scan(
+                    input.as_bytes(),
+                    Accumulator::default(),
+                    chunk_size,
+                    MAX_DIGITS,
+                );
+                let second = scan(
+                    input.as_bytes(),
+                    ConditionalAccumulator::default(),
+                    chunk_size,
+                    MAX_DIGITS,
+                );
+
+                assert_eq!(first.unwrap().sum, solution_1(path.to_owned()).unwrap());
+                assert_eq!(second.unwrap().sum, solution_2(path.to_owned()).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn scans_split_names_and_operands() {
+        let input = "xdon't()mul(12,345)do()mul(123,4)mul(1234,5)undo()mul(7,7)";
+        let expected = run(
+            ConditionalAccumulator::default(),
+            &match_instructions(input.to_owned()),
+        );
+
+        for chunk_size in 1..=input.len() {
+            let actual = scan(
+                input.as_bytes(),
+                ConditionalAccumulator::default(),
+                chunk_size,
+                MAX_DIGITS,
+            )
+            .unwrap();
+
+            assert_eq!(actual, expected, "{}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn scan_matches_whole_input() {
+        let input = read_input("./inputs/03/input.txt".to_owned()).unwrap();
+        let expected = run(
+            ConditionalAccumulator::default(),
+            &match_instructions(input.clone()),
+        );
+
+        for chunk_size in [7, 64, 1000] {
+            let actual = scan(
+                input.as_bytes(),
+                ConditionalAccumulator::default(),
+                chunk_size,
+                MAX_DIGITS,
+            )
+            .unwrap();
+
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn sums_past_i32_without_overflowing() {
+        let count = 3_000;
+        let input = "mul(999,999)".repeat(count);
+        let expected = count as Sum * 999 * 999;
+        assert!(expected > i32::MAX as Sum);
+
+        let actual = scan(input.as_bytes(), Accumulator::default(), 4096, MAX_DIGITS).unwrap();
+        assert_eq!(actual.sum, expected);
+
+        let actual = scan(
+            input.as_bytes(),
+            ConditionalAccumulator::default(),
+            4096,
+            MAX_DIGITS,
+        )
+        .unwrap();
+        assert_eq!(actual.sum, expected);
+    }
+
+    #[test]
+    fn annotates_instructions() {
+        let input = read_input("./inputs/03/example2.txt".to_owned()).unwrap();
+        let actual = annotate(&input)
+            .into_iter()
+            .map(|annotation| annotation.value)
+            .collect::<Vec<_>>();
+
+        let annotation = |highlight, total| Annotation { highlight, total };
+        let expected = Vec::from([
+            annotation(Highlight::Enabled, 8),
+            annotation(Highlight::Toggle, 8),
+            annotation(Highlight::Disabled, 8),
+            annotation(Highlight::Disabled, 8),
+            annotation(Highlight::Toggle, 8),
+            annotation(Highlight::Enabled, 48),
+        ]);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn renders_ansi() {
+        let actual = render(
+            "xmul(2,4)don't()mul(1,1)\ndo()<mul(3,3)",
+            RenderFormat::Ansi,
+        );
+        let expected = [
+            "x\x1b[32mmul(2,4)\x1b[0m\x1b[1mdon't()\x1b[0m\x1b[90mmul(1,1)\x1b[0m",
+            "   2: mul(2,4) │  8",
+            "  10: don't()  │  8",
+            "  17: mul(1,1) │  8",
+            "\x1b[1mdo()\x1b[0m<\x1b[32mmul(3,3)\x1b[0m",
+            "   1: do()     │  8",
+            "   6: mul(3,3) │ 17",
+        ]
+        .join("\n");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn renders_html() {
+        let actual = render("<mul(2,4)&", RenderFormat::Html);
+
+        assert!(
+            actual.contains("&lt;<span class=\"enabled\">mul(2,4)</span>&amp;\n   2: mul(2,4) │ 8")
+        );
+    }
 }
@@ -1,4 +1,11 @@
-use std::{collections::HashMap, error::Error, fs};
+use crate::random::next_random;
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    error::Error,
+    fs,
+    time::Instant,
+};
 
 const INPUT_PATH: &str = "./inputs/04/input.txt";
 
@@ -25,124 +32,657 @@ fn add(a: Coordinate, b: Coordinate) -> Coordinate {
     (a.0 + b.0, a.1 + b.1)
 }
 
-fn find_x_coordinates(input: Input) -> Vec<Coordinate> {
-    input
-        .iter()
-        .filter_map(|(coordinate, char)| -> Option<Coordinate> {
-            if *char == 'X' {
-                return Some(*coordinate);
-            }
+/// The eight horizontal, vertical and diagonal directions.
+const ALL_DIRECTIONS: [Coordinate; 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
 
-            None
-        })
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct WordMatch {
+    start: Coordinate,
+    direction: Coordinate,
+}
+
+fn word_coordinates(word_match: &WordMatch, length: usize) -> Vec<Coordinate> {
+    let (dx, dy) = word_match.direction;
+
+    (0..length as N)
+        .map(|step| add(word_match.start, (dx * step, dy * step)))
         .collect()
 }
 
-fn x_deltass() -> Vec<Vec<Coordinate>> {
-    Vec::from([
-        Vec::from([(0, 1), (0, 2), (0, 3)]),
-        Vec::from([(1, 1), (2, 2), (3, 3)]),
-        Vec::from([(1, 0), (2, 0), (3, 0)]),
-        Vec::from([(1, -1), (2, -2), (3, -3)]),
-        Vec::from([(0, -1), (0, -2), (0, -3)]),
-        Vec::from([(-1, -1), (-2, -2), (-3, -3)]),
-        Vec::from([(-1, 0), (-2, 0), (-3, 0)]),
-        Vec::from([(-1, 1), (-2, 2), (-3, 3)]),
-    ])
+/// Trie over the searched words with Aho–Corasick failure links.
+///
+/// `outputs[state]` lists the indices of all words ending in `state`.
+/// Empty words are left out, as they never match.
+struct AhoCorasick {
+    transitions: Vec<HashMap<char, usize>>,
+    failures: Vec<usize>,
+    outputs: Vec<Vec<usize>>,
 }
 
-fn check_xmas(input: &Input, coordinates: &Vec<Coordinate>) -> bool {
-    let word = coordinates
-        .iter()
-        .map(|coordinate| -> char { *input.get(coordinate).unwrap_or(&'.') })
-        .collect::<String>();
+fn build_aho_corasick(words: &[&str]) -> AhoCorasick {
+    let mut automaton = AhoCorasick {
+        transitions: Vec::from([HashMap::new()]),
+        failures: Vec::from([0]),
+        outputs: Vec::from([Vec::new()]),
+    };
+
+    for (index, word) in words.iter().enumerate() {
+        if word.is_empty() {
+            continue;
+        }
 
-    word == "XMAS"
+        let mut state = 0;
+
+        for c in word.chars() {
+            state = match automaton.transitions[state].get(&c) {
+                Some(next) => *next,
+                None => {
+                    let next = automaton.transitions.len();
+                    automaton.transitions.push(HashMap::new());
+                    automaton.failures.push(0);
+                    automaton.outputs.push(Vec::new());
+                    automaton.transitions[state].insert(c, next);
+                    next
+                }
+            };
+        }
+
+        automaton.outputs[state].push(index);
+    }
+
+    // Breadth first, so failures of shorter prefixes are known first.
+    let mut queue = automaton.transitions[0]
+        .values()
+        .copied()
+        .collect::<VecDeque<_>>();
+
+    while let Some(state) = queue.pop_front() {
+        let transitions = automaton.transitions[state]
+            .iter()
+            .map(|(c, next)| (*c, *next))
+            .collect::<Vec<_>>();
+
+        for (c, next) in transitions {
+            let failure = match state {
+                0 => 0,
+                _ => step_aho_corasick(&automaton, automaton.failures[state], c),
+            };
+
+            automaton.failures[next] = failure;
+            let inherited = automaton.outputs[failure].clone();
+            automaton.outputs[next].extend(inherited);
+            queue.push_back(next);
+        }
+    }
+
+    automaton
+}
+
+fn step_aho_corasick(automaton: &AhoCorasick, mut state: usize, c: char) -> usize {
+    loop {
+        if let Some(next) = automaton.transitions[state].get(&c) {
+            return *next;
+        }
+        if state == 0 {
+            return 0;
+        }
+        state = automaton.failures[state];
+    }
 }
 
-fn find_xmas(input: Input) -> Vec<Vec<Coordinate>> {
-    let x_coordinates = find_x_coordinates(input.clone());
+/// Cells of the grid line running through `start` in `direction`, from its first cell on.
+fn grid_line(input: &Input, start: Coordinate, direction: Coordinate) -> Vec<(Coordinate, char)> {
+    let mut line = Vec::new();
+    let mut coordinate = start;
+
+    while let Some(c) = input.get(&coordinate) {
+        line.push((coordinate, *c));
+        coordinate = add(coordinate, direction);
+    }
+
+    line
+}
+
+fn is_palindrome(word: &str) -> bool {
+    word.chars().eq(word.chars().rev())
+}
+
+/// Finds all `words` along every line of the grid in each of the `directions` in one pass.
+///
+/// A palindrome read backwards covers the same cells as read forwards,
+/// so only one of the two readings is reported.
+/// Single letters are only reported in the first direction.
+fn find_words(input: &Input, words: &[&str], directions: &[Coordinate]) -> Vec<(usize, WordMatch)> {
+    let automaton = build_aho_corasick(words);
+    let lengths = words
+        .iter()
+        .map(|word| word.chars().count())
+        .collect::<Vec<_>>();
+    let palindromes = words
+        .iter()
+        .map(|word| is_palindrome(word))
+        .collect::<Vec<_>>();
+
+    let mut matches = Vec::new();
+
+    for direction in directions {
+        let reverse = (-direction.0, -direction.1);
+        let reverse_searched = directions.contains(&reverse) && reverse < *direction;
 
-    let candidates = x_coordinates
+        let line_starts = input
+            .keys()
+            .filter(|coordinate| !input.contains_key(&add(**coordinate, reverse)));
+
+        for line_start in line_starts {
+            let line = grid_line(input, *line_start, *direction);
+            let mut state = 0;
+
+            for (index, (_, c)) in line.iter().enumerate() {
+                state = step_aho_corasick(&automaton, state, *c);
+
+                for word in automaton.outputs[state].iter() {
+                    let duplicate = match lengths[*word] {
+                        1 => Some(direction) != directions.first(),
+                        _ => palindromes[*word] && reverse_searched,
+                    };
+                    if duplicate {
+                        continue;
+                    }
+
+                    let start = line[index + 1 - lengths[*word]].0;
+                    matches.push((
+                        *word,
+                        WordMatch {
+                            start,
+                            direction: *direction,
+                        },
+                    ));
+                }
+            }
+        }
+    }
+
+    matches.sort();
+    matches
+}
+
+fn find_word(input: &Input, word: &str, directions: &[Coordinate]) -> Vec<WordMatch> {
+    find_words(input, &[word], directions)
         .into_iter()
-        .flat_map(|start_coordinate| -> Vec<Vec<Coordinate>> {
-            x_deltass()
-                .into_iter()
-                .map(|deltas| -> Vec<Coordinate> {
-                    Vec::from([start_coordinate])
-                        .into_iter()
-                        .chain(
-                            deltas
-                                .into_iter()
-                                .map(|delta| -> Coordinate { add(start_coordinate, delta) }),
-                        )
-                        .collect()
-                })
-                .collect()
-        })
-        .filter(|candidate| -> bool { check_xmas(&input, candidate) })
-        .collect::<Vec<Vec<Coordinate>>>();
+        .map(|(_, word_match)| word_match)
+        .collect()
+}
 
-    return candidates;
+/// The cells of every `XMAS`, for rendering. `count_xmas` only counts, without allocating.
+fn find_xmas(input: &Input) -> Vec<Vec<Coordinate>> {
+    find_word(input, "XMAS", &ALL_DIRECTIONS)
+        .iter()
+        .map(|word_match| word_coordinates(word_match, 4))
+        .collect()
 }
 
 fn first() -> Result<(), Box<dyn Error>> {
-    let input = read_input(INPUT_PATH)?;
-    let wanted = find_xmas(input).len();
+    let grid = read_byte_grid(INPUT_PATH)?;
+    let wanted = count_xmas(&grid);
     println!("{}", wanted);
     Ok(())
 }
 
-fn find_a_coordinates(input: Input) -> Vec<Coordinate> {
-    input
-        .iter()
-        .filter_map(|(coordinate, char)| -> Option<Coordinate> {
-            if *char == 'A' {
-                return Some(*coordinate);
-            }
+/// Matches any letter in a template.
+const WILDCARD: char = '.';
+
+/// The X-MAS shape of the second part.
+const X_MAS: &str = "M.S / .A. / M.S";
+
+/// A 2D pattern of letters, without its wildcard cells.
+///
+/// Cells are sorted and offset so that the smallest x and y are `0`.
+#[derive(Clone, Debug, PartialEq)]
+struct Template {
+    cells: Vec<(Coordinate, char)>,
+}
+
+fn normalize_template(mut cells: Vec<(Coordinate, char)>) -> Template {
+    let min_x = cells.iter().map(|((x, _), _)| *x).min().unwrap_or(0);
+    let min_y = cells.iter().map(|((_, y), _)| *y).min().unwrap_or(0);
+
+    for (coordinate, _) in cells.iter_mut() {
+        *coordinate = add(*coordinate, (-min_x, -min_y));
+    }
+    cells.sort();
+
+    Template { cells }
+}
+
+/// Parses rows separated by newlines or ` / `, where `.` is a wildcard.
+fn parse_template(text: &str) -> Template {
+    let cells = text
+        .split(['\n', '/'])
+        .map(|row| row.trim())
+        .filter(|row| !row.is_empty())
+        .enumerate()
+        .flat_map(|(y, row)| -> Vec<(Coordinate, char)> {
+            row.chars()
+                .enumerate()
+                .filter(|(_, c)| *c != WILDCARD)
+                .map(|(x, c)| ((x as N, y as N), c))
+                .collect()
+        })
+        .collect();
+
+    normalize_template(cells)
+}
+
+/// Quarter turns clockwise, applied after an optional mirroring along the y axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Orientation {
+    quarter_turns: usize,
+    reflected: bool,
+}
 
-            None
+fn all_orientations() -> Vec<Orientation> {
+    [false, true]
+        .into_iter()
+        .flat_map(|reflected| {
+            (0..4).map(move |quarter_turns| Orientation {
+                quarter_turns,
+                reflected,
+            })
         })
         .collect()
 }
 
-fn check_x_mas(input: &Input, a_coordinate: Coordinate) -> bool {
-    let w1 = [
-        *input.get(&add(a_coordinate, (-1, -1))).unwrap_or(&'.'),
-        'A',
-        *input.get(&add(a_coordinate, (1, 1))).unwrap_or(&'.'),
-    ]
-    .into_iter()
-    .collect::<String>();
+fn orient_template(template: &Template, orientation: &Orientation) -> Template {
+    let cells = template
+        .cells
+        .iter()
+        .map(|((x, y), c)| -> (Coordinate, char) {
+            let mut coordinate = match orientation.reflected {
+                true => (-x, *y),
+                false => (*x, *y),
+            };
+            for _ in 0..orientation.quarter_turns {
+                coordinate = (-coordinate.1, coordinate.0);
+            }
+
+            (coordinate, *c)
+        })
+        .collect();
+
+    normalize_template(cells)
+}
 
-    let w2 = [
-        *input.get(&add(a_coordinate, (-1, 1))).unwrap_or(&'.'),
-        'A',
-        *input.get(&add(a_coordinate, (1, -1))).unwrap_or(&'.'),
-    ]
-    .into_iter()
-    .collect::<String>();
+#[derive(Clone, Debug, PartialEq)]
+struct TemplateMatch {
+    origin: Coordinate,
+    orientation: Orientation,
+}
 
-    (w1 == "MAS" || w1 == "SAM") && (w2 == "MAS" || w2 == "SAM")
+fn template_matches_at(input: &Input, template: &Template, origin: Coordinate) -> bool {
+    template
+        .cells
+        .iter()
+        .all(|(offset, c)| input.get(&add(origin, *offset)) == Some(c))
 }
 
-fn find_x_mas(input: Input) -> Vec<Coordinate> {
-    let a_coordinates = find_a_coordinates(input.clone());
+/// Finds `template` in all of the given `orientations`.
+///
+/// Orientations that turn a symmetric template into one already searched are skipped,
+/// so every occurrence is reported once.
+fn find_template(
+    input: &Input,
+    template: &Template,
+    orientations: &[Orientation],
+) -> Vec<TemplateMatch> {
+    let mut searched: Vec<Template> = Vec::new();
+    let mut matches = Vec::new();
+
+    for orientation in orientations {
+        let oriented = orient_template(template, orientation);
+        if searched.contains(&oriented) {
+            continue;
+        }
+
+        let Some((anchor, anchor_char)) = oriented.cells.first() else {
+            continue;
+        };
 
-    a_coordinates
+        let mut origins = input
+            .iter()
+            .filter(|(_, c)| *c == anchor_char)
+            .map(|(coordinate, _)| add(*coordinate, (-anchor.0, -anchor.1)))
+            .filter(|origin| template_matches_at(input, &oriented, *origin))
+            .collect::<Vec<_>>();
+        origins.sort();
+
+        matches.extend(origins.into_iter().map(|origin| TemplateMatch {
+            origin,
+            orientation: *orientation,
+        }));
+        searched.push(oriented);
+    }
+
+    matches
+}
+
+/// The center of every X-MAS, for rendering. `count_x_mas` only counts, without allocating.
+fn find_x_mas(input: &Input) -> Vec<Coordinate> {
+    find_template(input, &parse_template(X_MAS), &all_orientations())
         .into_iter()
-        .filter(|a_coordinate| check_x_mas(&input, *a_coordinate))
+        .map(|template_match| add(template_match.origin, (1, 1)))
         .collect()
 }
 
 fn second() -> Result<(), Box<dyn Error>> {
-    let input = read_input(INPUT_PATH)?;
-    let wanted = find_x_mas(input).len();
+    let grid = read_byte_grid(INPUT_PATH)?;
+    let wanted = count_x_mas(&grid);
     println!("{}", wanted);
     Ok(())
 }
 
+/// The grid as rows of bytes laid out one after another.
+///
+/// Unlike `Input` this needs no hashing per lookup and lets rows be scanned as slices.
+struct ByteGrid {
+    width: usize,
+    bytes: Vec<u8>,
+}
+
+fn parse_byte_grid(input: &str) -> Result<ByteGrid, Box<dyn Error>> {
+    let rows = input.lines().collect::<Vec<_>>();
+    let width = rows.first().map_or(0, |row| row.len());
+
+    if rows.iter().any(|row| row.len() != width) {
+        return Err("Rows of the grid differ in length.".into());
+    }
+
+    Ok(ByteGrid {
+        width,
+        bytes: rows.concat().into_bytes(),
+    })
+}
+
+fn read_byte_grid(path: &str) -> Result<ByteGrid, Box<dyn Error>> {
+    parse_byte_grid(&fs::read_to_string(path)?)
+}
+
+fn grid_rows(grid: &ByteGrid) -> impl Iterator<Item = &[u8]> {
+    grid.bytes.chunks_exact(grid.width.max(1))
+}
+
+/// Whether four bytes read `XMAS` in either direction, without branching so that loops vectorize.
+fn is_xmas(a: u8, b: u8, c: u8, d: u8) -> bool {
+    let forwards = (a == b'X') & (b == b'M') & (c == b'A') & (d == b'S');
+    let backwards = (a == b'S') & (b == b'A') & (c == b'M') & (d == b'X');
+    forwards | backwards
+}
+
+/// Counts the positions where the four slices read `XMAS` in either direction.
+///
+/// Chunks of 255 are counted with `u8` lanes, which lets the comparisons vectorize.
+fn count_aligned(a: &[u8], b: &[u8], c: &[u8], d: &[u8]) -> usize {
+    a.chunks(255)
+        .zip(b.chunks(255))
+        .zip(c.chunks(255))
+        .zip(d.chunks(255))
+        .map(|(((a, b), c), d)| -> usize {
+            a.iter()
+                .zip(b)
+                .zip(c)
+                .zip(d)
+                .map(|(((a, b), c), d)| is_xmas(*a, *b, *c, *d) as u8)
+                .sum::<u8>() as usize
+        })
+        .sum()
+}
+
+fn tail(row: &[u8], offset: usize) -> &[u8] {
+    row.get(offset..).unwrap_or_default()
+}
+
+/// Counts `XMAS` in all directions by comparing row slices.
+///
+/// Every line is read once and matched forwards and backwards.
+/// A row is compared against itself shifted by one to three cells,
+/// columns and diagonals compare four consecutive rows, the diagonals shifted against each other.
+fn count_xmas(grid: &ByteGrid) -> usize {
+    let rows = grid_rows(grid).collect::<Vec<_>>();
+
+    let horizontal = rows
+        .iter()
+        .map(|row| count_aligned(row, tail(row, 1), tail(row, 2), tail(row, 3)))
+        .sum::<usize>();
+
+    let others = rows
+        .windows(4)
+        .map(|rows| -> usize {
+            let [r0, r1, r2, r3] = [rows[0], rows[1], rows[2], rows[3]];
+
+            count_aligned(r0, r1, r2, r3)
+                + count_aligned(r0, tail(r1, 1), tail(r2, 2), tail(r3, 3))
+                + count_aligned(tail(r0, 3), tail(r1, 2), tail(r2, 1), r3)
+        })
+        .sum::<usize>();
+
+    horizontal + others
+}
+
+/// Whether two bytes read `MAS` around an `A`, in either direction.
+fn is_mas(a: u8, b: u8) -> bool {
+    ((a == b'M') & (b == b'S')) | ((a == b'S') & (b == b'M'))
+}
+
+/// Counts X-MAS shapes centered on `row`, with `above` and `below` its neighbouring rows.
+///
+/// Like `count_aligned` this compares shifted slices in chunks with `u8` lanes.
+fn count_x_mas_in_row(above: &[u8], row: &[u8], below: &[u8]) -> usize {
+    if row.len() < 3 {
+        return 0;
+    }
+
+    let length = row.len() - 2;
+    let (top_left, top_right) = (&above[..length], &above[2..length + 2]);
+    let (bottom_left, bottom_right) = (&below[..length], &below[2..length + 2]);
+    let centers = &row[1..length + 1];
+
+    (0..length)
+        .step_by(255)
+        .map(|start| -> usize {
+            (start..(start + 255).min(length))
+                .map(|x| {
+                    ((centers[x] == b'A')
+                        & is_mas(top_left[x], bottom_right[x])
+                        & is_mas(top_right[x], bottom_left[x])) as u8
+                })
+                .sum::<u8>() as usize
+        })
+        .sum()
+}
+
+/// Counts X-MAS shapes by checking both diagonals around every inner `A`.
+fn count_x_mas(grid: &ByteGrid) -> usize {
+    grid_rows(grid)
+        .collect::<Vec<_>>()
+        .windows(3)
+        .map(|rows| count_x_mas_in_row(rows[0], rows[1], rows[2]))
+        .sum()
+}
+
+/// A `size`×`size` grid of pseudo random letters from `XMAS`.
+fn generate_grid(size: usize, seed: u64) -> String {
+    let mut state = seed;
+    let mut rows = Vec::with_capacity(size);
+
+    for _ in 0..size {
+        let row = (0..size)
+            .map(|_| -> char { ['X', 'M', 'A', 'S'][next_random(&mut state, 4) as usize] })
+            .collect::<String>();
+        rows.push(row);
+    }
+
+    rows.join("\n")
+}
+
+fn time<T>(label: &str, cells: usize, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+
+    println!(
+        "{}: {:?} ({:.2} ns/cell)",
+        label,
+        elapsed,
+        elapsed.as_nanos() as f64 / cells as f64
+    );
+
+    result
+}
+
+/// Compares the map based search with the byte grid counters.
+///
+/// The map based search needs gigabytes for a 10k×10k grid,
+/// so it runs on a tenth of the side length and is compared per cell.
+fn bench(size: usize) -> Result<(), Box<dyn Error>> {
+    let small_size = size / 10;
+    let small = generate_grid(small_size, 4);
+    let small_cells = small_size * small_size;
+    let small_map = small
+        .lines()
+        .enumerate()
+        .flat_map(|(y, line)| {
+            line.chars()
+                .enumerate()
+                .map(move |(x, c)| ((x as N, y as N), c))
+        })
+        .collect::<Input>();
+
+    let xmas = time(
+        &format!("map xmas {0}x{0}", small_size),
+        small_cells,
+        || find_xmas(&small_map).len(),
+    );
+    let x_mas = time(
+        &format!("map x-mas {0}x{0}", small_size),
+        small_cells,
+        || find_x_mas(&small_map).len(),
+    );
+    let small_grid = parse_byte_grid(&small)?;
+    assert_eq!(xmas, count_xmas(&small_grid));
+    assert_eq!(x_mas, count_x_mas(&small_grid));
+
+    let grid = parse_byte_grid(&generate_grid(size, 4))?;
+    let cells = size * size;
+    let xmas = time(&format!("bytes xmas {0}x{0}", size), cells, || {
+        count_xmas(&grid)
+    });
+    let x_mas = time(&format!("bytes x-mas {0}x{0}", size), cells, || {
+        count_x_mas(&grid)
+    });
+    println!("{} XMAS, {} X-MAS", xmas, x_mas);
+
+    Ok(())
+}
+
+fn template_cells(template: &Template, template_match: &TemplateMatch) -> Vec<Coordinate> {
+    orient_template(template, &template_match.orientation)
+        .cells
+        .iter()
+        .map(|(offset, _)| add(template_match.origin, *offset))
+        .collect()
+}
+
+/// How many of the matches each cell is part of.
+fn match_counts(matches: &[Vec<Coordinate>]) -> HashMap<Coordinate, usize> {
+    let mut counts = HashMap::new();
+
+    for coordinate in matches.iter().flatten() {
+        *counts.entry(*coordinate).or_insert(0) += 1;
+    }
+
+    counts
+}
+
+fn render_grid(input: &Input, render_cell: impl Fn(&Coordinate, char) -> char) -> String {
+    let width = input.keys().map(|(x, _)| x + 1).max().unwrap_or(0);
+    let height = input.keys().map(|(_, y)| y + 1).max().unwrap_or(0);
+
+    (0..height)
+        .map(|y| -> String {
+            (0..width)
+                .map(|x| match input.get(&(x, y)) {
+                    Some(c) => render_cell(&(x, y), *c),
+                    None => ' ',
+                })
+                .collect()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The grid with every letter that is not part of a match replaced by `.`.
+fn render_matches(input: &Input, matches: &[Vec<Coordinate>]) -> String {
+    let counts = match_counts(matches);
+
+    render_grid(input, |coordinate, c| {
+        match counts.contains_key(coordinate) {
+            true => c,
+            false => '.',
+        }
+    })
+}
+
+/// The grid with every letter replaced by the number of matches it is part of.
+///
+/// Cells without matches show `.`, cells with more than nine show `+`.
+fn render_heatmap(input: &Input, matches: &[Vec<Coordinate>]) -> String {
+    let counts = match_counts(matches);
+
+    render_grid(input, |coordinate, _| match counts.get(coordinate) {
+        None => '.',
+        Some(count) => char::from_digit(*count as u32, 10).unwrap_or('+'),
+    })
+}
+
+fn visualize(heatmap: bool) -> Result<(), Box<dyn Error>> {
+    let input = read_input(INPUT_PATH)?;
+    let render = match heatmap {
+        true => render_heatmap,
+        false => render_matches,
+    };
+
+    let xmas = find_xmas(&input);
+    println!("{}\n", render(&input, &xmas));
+
+    let template = parse_template(X_MAS);
+    let x_mas = find_template(&input, &template, &all_orientations())
+        .iter()
+        .map(|template_match| template_cells(&template, template_match))
+        .collect::<Vec<_>>();
+    println!("{}", render(&input, &x_mas));
+
+    Ok(())
+}
+
 pub fn main() -> Result<(), Box<dyn Error>> {
+    if env::args().any(|arg| arg == "--show") {
+        return visualize(false);
+    }
+    if env::args().any(|arg| arg == "--heatmap") {
+        return visualize(true);
+    }
+    if env::args().any(|arg| arg == "--bench") {
+        return bench(10_000);
+    }
+
     println!("01-1:");
     first()?;
     println!("01-2:");
@@ -151,7 +691,24 @@ pub fn main() -> Result<(), Box<dyn Error>> {
 
 #[cfg(test)]
 mod tests {
-    use super::{find_x_mas, find_xmas, read_input};
+    use super::{
+        all_orientations, count_x_mas, count_xmas, find_template, find_word, find_words,
+        find_x_mas, find_xmas, generate_grid, orient_template, parse_byte_grid, parse_template,
+        read_byte_grid, read_input, render_heatmap, render_matches, template_cells, Coordinate,
+        Input, Orientation, Template, WordMatch, ALL_DIRECTIONS, INPUT_PATH, X_MAS,
+    };
+
+    fn grid(rows: &[&str]) -> Input {
+        rows.iter()
+            .enumerate()
+            .flat_map(|(y, row)| -> Vec<(Coordinate, char)> {
+                row.chars()
+                    .enumerate()
+                    .map(|(x, c)| ((x as i32, y as i32), c))
+                    .collect()
+            })
+            .collect()
+    }
 
     const EXAMPLE_PATH: &str = "./inputs/04/example.txt";
 
@@ -159,7 +716,7 @@ mod tests {
     fn should_calculate_first_example() {
         let input = read_input(EXAMPLE_PATH).unwrap();
 
-        let actual = find_xmas(input).len();
+        let actual = find_xmas(&input).len();
         let expected = 18;
 
         assert_eq!(actual, expected);
@@ -169,9 +726,247 @@ mod tests {
     fn should_calculate_second_example() {
         let input = read_input(EXAMPLE_PATH).unwrap();
 
-        let actual = find_x_mas(input).len();
+        let actual = find_x_mas(&input).len();
         let expected = 9;
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn finds_word_with_start_and_direction() {
+        let input = grid(&["XMAS", "....", "SAMX"]);
+
+        let actual = find_word(&input, "XMAS", &ALL_DIRECTIONS);
+        let expected = Vec::from([
+            WordMatch {
+                start: (0, 0),
+                direction: (1, 0),
+            },
+            WordMatch {
+                start: (3, 2),
+                direction: (-1, 0),
+            },
+        ]);
+
+        assert_eq!(actual, expected);
+        assert_eq!(find_word(&input, "XMAS", &[(1, 0)]).len(), 1);
+    }
+
+    #[test]
+    fn counts_palindromes_once() {
+        let input = grid(&["ABA", "B.B", "ABA"]);
+
+        assert_eq!(find_word(&input, "ABA", &ALL_DIRECTIONS).len(), 4);
+        assert_eq!(find_word(&input, "ABA", &[(-1, 0)]).len(), 2);
+        assert_eq!(find_word(&input, "A", &ALL_DIRECTIONS).len(), 4);
+    }
+
+    #[test]
+    fn finds_several_words_in_one_pass() {
+        let input = read_input("./inputs/04/example.txt").unwrap();
+
+        let actual = find_words(&input, &["XMAS", "MAS", "SAMX"], &ALL_DIRECTIONS);
+        let count = |word| actual.iter().filter(|(index, _)| *index == word).count();
+
+        assert_eq!(count(0), 18);
+        assert_eq!(count(1), find_word(&input, "MAS", &ALL_DIRECTIONS).len());
+        assert_eq!(count(2), 18);
+    }
+
+    #[test]
+    fn finds_no_empty_words() {
+        let input = read_input("./inputs/04/example.txt").unwrap();
+
+        assert_eq!(find_word(&input, "", &ALL_DIRECTIONS), Vec::new());
+
+        let actual = find_words(&input, &["", "XMAS"], &ALL_DIRECTIONS);
+        assert!(actual.iter().all(|(index, _)| *index == 1));
+        assert_eq!(actual.len(), 18);
+    }
+
+    #[test]
+    fn parses_templates() {
+        let expected = Template {
+            cells: Vec::from([
+                ((0, 0), 'M'),
+                ((0, 2), 'M'),
+                ((1, 1), 'A'),
+                ((2, 0), 'S'),
+                ((2, 2), 'S'),
+            ]),
+        };
+
+        assert_eq!(parse_template(X_MAS), expected);
+        assert_eq!(parse_template("M.S\n.A.\nM.S\n"), expected);
+    }
+
+    #[test]
+    fn orients_templates() {
+        let template = parse_template("AB");
+        let orient = |quarter_turns, reflected| {
+            orient_template(
+                &template,
+                &Orientation {
+                    quarter_turns,
+                    reflected,
+                },
+            )
+        };
+
+        assert_eq!(orient(0, false), template);
+        assert_eq!(orient(1, false), parse_template("A/B"));
+        assert_eq!(orient(2, false), parse_template("BA"));
+        assert_eq!(orient(3, false), parse_template("B/A"));
+        assert_eq!(orient(0, true), parse_template("BA"));
+    }
+
+    #[test]
+    fn finds_x_mas_in_every_orientation_once() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let template = parse_template(X_MAS);
+
+        let orientations = all_orientations();
+
+        assert_eq!(find_template(&input, &template, &orientations).len(), 9);
+        assert_eq!(
+            find_template(&input, &template, &orientations[..4]).len(),
+            9
+        );
+        assert_eq!(
+            find_template(&input, &template, &orientations[..1]).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn finds_plus_mas() {
+        let input = grid(&["XMXS", "MASA", "XSXM"]);
+        let template = parse_template(".M. / MAS / .S.");
+
+        let actual = find_template(&input, &template, &all_orientations())
+            .into_iter()
+            .map(|template_match| template_match.origin)
+            .collect::<Vec<_>>();
+
+        assert_eq!(actual, Vec::from([(0, 0)]));
+    }
+
+    #[test]
+    fn renders_xmas_matches() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let actual = render_matches(&input, &find_xmas(&input));
+
+        let expected = [
+            "....XXMAS.",
+            ".SAMXMS...",
+            "...S..A...",
+            "..A.A.MS.X",
+            "XMASAMX.MM",
+            "X.....XA.A",
+            "S.S.S.S.SS",
+            ".A.A.A.A.A",
+            "..M.M.M.MM",
+            ".X.X.XMASX",
+        ]
+        .join("\n");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn renders_x_mas_matches() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let template = parse_template(X_MAS);
+        let matches = find_template(&input, &template, &all_orientations())
+            .iter()
+            .map(|template_match| template_cells(&template, template_match))
+            .collect::<Vec<_>>();
+        let actual = render_matches(&input, &matches);
+
+        let expected = [
+            ".M.S......",
+            "..A..MSMS.",
+            ".M.S.MAA..",
+            "..A.ASMSM.",
+            ".M.S.M....",
+            "..........",
+            "S.S.S.S.S.",
+            ".A.A.A.A..",
+            "M.M.M.M.M.",
+            "..........",
+        ]
+        .join("\n");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn renders_heatmap() {
+        let input = grid(&["XMASAMX", "......."]);
+        let actual = render_heatmap(&input, &find_xmas(&input));
+
+        assert_eq!(actual, "1112111\n.......");
+    }
+
+    #[test]
+    fn byte_grid_counts_match_map_search() {
+        for path in [EXAMPLE_PATH, INPUT_PATH] {
+            let input = read_input(path).unwrap();
+            let grid = read_byte_grid(path).unwrap();
+
+            assert_eq!(count_xmas(&grid), find_xmas(&input).len());
+            assert_eq!(count_x_mas(&grid), find_x_mas(&input).len());
+        }
+
+        let generated = generate_grid(50, 1);
+        let grid = parse_byte_grid(&generated).unwrap();
+        let input = generated
+            .lines()
+            .enumerate()
+            .flat_map(|(y, line)| -> Vec<(Coordinate, char)> {
+                line.chars()
+                    .enumerate()
+                    .map(|(x, c)| ((x as i32, y as i32), c))
+                    .collect()
+            })
+            .collect::<Input>();
+
+        assert_eq!(count_xmas(&grid), find_xmas(&input).len());
+        assert_eq!(count_x_mas(&grid), find_x_mas(&input).len());
+    }
+
+    #[test]
+    fn byte_grid_counts_handle_narrow_grids() {
+        let grids: [&[&str]; 6] = [
+            &["X", "M", "A", "S"],
+            &["SAMX"],
+            &["XMA"],
+            &["XMASX", "MMAS.", "AXAA.", "SASMS"],
+            &["M.S", ".A.", "M.S"],
+            &["MS", "AA"],
+        ];
+
+        for rows in grids {
+            let input = grid(rows);
+            let byte_grid = parse_byte_grid(&rows.join("\n")).unwrap();
+
+            assert_eq!(
+                count_xmas(&byte_grid),
+                find_xmas(&input).len(),
+                "{:?}",
+                rows
+            );
+            assert_eq!(
+                count_x_mas(&byte_grid),
+                find_x_mas(&input).len(),
+                "{:?}",
+                rows
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_ragged_byte_grid() {
+        assert!(parse_byte_grid("XMAS\nXM").is_err());
+    }
 }
@@ -1,14 +1,15 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    env,
     error::Error,
-    fs,
+    fmt, fs, io,
 };
 
 const INPUT_PATH: &str = "./inputs/05/input.txt";
 
 type Page = i32;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 struct PageOrdering {
     before: Page,
     after: Page,
@@ -95,70 +96,1020 @@ fn filter_ordered_updates(after_to_befores: BeforeMap, updates: Vec<Update>) ->
         .collect()
 }
 
-fn get_middle(update: Update) -> Page {
-    let index = update.len() / 2;
-    *update.get(index).unwrap_or(&0)
+/// Why a query about an update has no answer.
+#[derive(Debug, PartialEq)]
+enum QueryError {
+    EmptyUpdate,
+    /// An update of even length has no single middle page.
+    EvenLength {
+        length: usize,
+    },
+    PageNotInUpdate {
+        page: Page,
+    },
+    /// The rules don't order the update far enough to tell its middle page.
+    NoMedian,
+    /// The rules among these pages form a cycle, so none of them comes first.
+    Cycle {
+        pages: Vec<Page>,
+    },
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::EmptyUpdate => write!(f, "The update is empty."),
+            QueryError::EvenLength { length } => {
+                write!(f, "The update has an even length of {}.", length)
+            }
+            QueryError::PageNotInUpdate { page } => {
+                write!(f, "Page {} is not part of the update.", page)
+            }
+            QueryError::NoMedian => write!(f, "The rules don't determine a middle page."),
+            QueryError::Cycle { pages } => {
+                write!(f, "The rules for pages {:?} form a cycle.", pages)
+            }
+        }
+    }
+}
+
+impl Error for QueryError {}
+
+fn check_odd_length(update: &Update) -> Result<(), QueryError> {
+    match update.len() {
+        0 => Err(QueryError::EmptyUpdate),
+        length if length % 2 == 0 => Err(QueryError::EvenLength { length }),
+        _ => Ok(()),
+    }
+}
+
+fn get_middle(update: Update) -> Result<Page, QueryError> {
+    check_odd_length(&update)?;
+
+    Ok(update[update.len() / 2])
 }
 
-fn solution_1(input: Input) -> Page {
+fn solution_1(input: Input) -> Result<Page, QueryError> {
     let ordered_updates = filter_ordered_updates(before_map(input.0), input.1);
 
-    ordered_updates
-        .into_iter()
-        .map(|update| get_middle(update))
-        .sum::<Page>()
+    ordered_updates.into_iter().map(get_middle).sum()
 }
 
 fn first() -> Result<(), Box<dyn Error>> {
     let input = read_input(INPUT_PATH).unwrap();
-    let wanted = solution_1(input);
+    let wanted = solution_1(input)?;
     println!("{}", wanted);
     Ok(())
 }
 
-fn find_swap(after_to_befores: &BeforeMap, update: &Update) -> Option<(usize, usize)> {
+/// Why an update could not be ordered.
+#[derive(Debug, PartialEq)]
+enum OrderError {
+    /// The rules among these pages of the update form a cycle.
+    Cycle { pages: Vec<Page> },
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderError::Cycle { pages } => {
+                write!(f, "The rules for pages {:?} form a cycle.", pages)
+            }
+        }
+    }
+}
+
+impl Error for OrderError {}
+
+/// Orders an update by a topological sort of the rules among its pages.
+///
+/// Uses Kahn's algorithm. Whenever several pages are ready,
+/// the one that comes first in the given update is taken next.
+fn order_update(after_to_befores: &BeforeMap, update: Update) -> Result<Update, OrderError> {
+    // afters[i] lists indices of pages that must come after update[i].
+    let mut afters: Vec<Vec<usize>> = vec![Vec::new(); update.len()];
+    let mut before_counts: Vec<usize> = vec![0; update.len()];
+
     for (index, page) in update.iter().enumerate() {
         let Some(befores) = after_to_befores.get(page) else {
             continue;
         };
 
-        for (other_index, other_page) in update[index + 1..].iter().enumerate() {
+        for (other_index, other_page) in update.iter().enumerate() {
             if befores.contains(other_page) {
-                return Some((index, other_index + index + 1));
+                afters[other_index].push(index);
+                before_counts[index] += 1;
+            }
+        }
+    }
+
+    let mut ready = (0..update.len())
+        .filter(|index| before_counts[*index] == 0)
+        .collect::<BTreeSet<_>>();
+    let mut ordered = Vec::with_capacity(update.len());
+
+    while let Some(index) = ready.pop_first() {
+        ordered.push(update[index]);
+
+        for after in afters[index].iter() {
+            before_counts[*after] -= 1;
+            if before_counts[*after] == 0 {
+                ready.insert(*after);
             }
         }
     }
 
-    None
+    if ordered.len() < update.len() {
+        let leftover = (0..update.len())
+            .filter(|index| before_counts[*index] > 0)
+            .map(|index| update[index])
+            .collect::<Vec<_>>();
+
+        return Err(OrderError::Cycle {
+            pages: pages_on_cycles(after_to_befores, &leftover),
+        });
+    }
+
+    Ok(ordered)
+}
+
+/// The `pages` that lie on a cycle of rules among themselves, in their given order.
+///
+/// Pages that are only stuck behind a cycle are left out.
+fn pages_on_cycles(after_to_befores: &BeforeMap, pages: &[Page]) -> Vec<Page> {
+    let mut graph: RuleGraph = pages.iter().map(|page| (*page, BTreeSet::new())).collect();
+    for page in pages.iter() {
+        for before in after_to_befores.get(page).into_iter().flatten() {
+            if graph.contains_key(before) {
+                graph.entry(*before).or_default().insert(*page);
+            }
+        }
+    }
+
+    let cyclic = strongly_connected_components(&graph)
+        .into_iter()
+        .filter(|component| component.len() > 1 || graph[&component[0]].contains(&component[0]))
+        .flatten()
+        .collect::<HashSet<_>>();
+
+    pages
+        .iter()
+        .filter(|page| cyclic.contains(page))
+        .copied()
+        .collect()
+}
+
+/// Every page mapped to all pages that must come after it, directly or through other rules.
+struct Closure {
+    afters: HashMap<Page, HashSet<Page>>,
+    components: Vec<Vec<Page>>,
 }
 
-fn order_update(after_to_befores: &BeforeMap, mut update: Update) -> Update {
-    while let Some((a, b)) = find_swap(after_to_befores, &update) {
-        update.swap(a, b);
+fn transitive_closure(page_orderings: &[PageOrdering]) -> Closure {
+    let graph = rule_graph(page_orderings, &all_pages(page_orderings));
+
+    let afters = graph
+        .keys()
+        .map(|page| -> (Page, HashSet<Page>) {
+            let mut afters = HashSet::new();
+            let mut stack = Vec::from([*page]);
+
+            while let Some(page) = stack.pop() {
+                for after in graph[&page].iter() {
+                    if afters.insert(*after) {
+                        stack.push(*after);
+                    }
+                }
+            }
+
+            (*page, afters)
+        })
+        .collect();
+
+    Closure {
+        afters,
+        components: strongly_connected_components(&graph),
+    }
+}
+
+/// Whether the rules require `a` to come before `b`.
+///
+/// Fails if `a` and `b` share a cycle, as then each must come before the other.
+fn precedes(closure: &Closure, a: Page, b: Page) -> Result<bool, QueryError> {
+    if !closure
+        .afters
+        .get(&a)
+        .is_some_and(|afters| afters.contains(&b))
+    {
+        return Ok(false);
     }
 
-    return update;
+    match closure
+        .components
+        .iter()
+        .find(|component| component.contains(&a) && component.contains(&b))
+    {
+        Some(component) if a != b || closure.afters[&a].contains(&a) => Err(QueryError::Cycle {
+            pages: component.clone(),
+        }),
+        _ => Ok(true),
+    }
 }
 
-fn solution_2(input: Input) -> Page {
+/// Pages of the update that must come before `page` by the rules among the update's pages.
+fn predecessors_in_update(
+    after_to_befores: &BeforeMap,
+    page: Page,
+    update: &Update,
+) -> HashSet<Page> {
+    let mut predecessors = HashSet::new();
+    let mut stack = Vec::from([page]);
+
+    while let Some(page) = stack.pop() {
+        let Some(befores) = after_to_befores.get(&page) else {
+            continue;
+        };
+
+        for before in befores.iter() {
+            if update.contains(before) && predecessors.insert(*before) {
+                stack.push(*before);
+            }
+        }
+    }
+
+    predecessors
+}
+
+/// How many pages of the update must come before `page`.
+///
+/// Fails if `page` lies on a cycle of rules among the update's pages.
+fn rank_in_update(
+    after_to_befores: &BeforeMap,
+    page: Page,
+    update: &Update,
+) -> Result<usize, QueryError> {
+    if !update.contains(&page) {
+        return Err(QueryError::PageNotInUpdate { page });
+    }
+
+    let predecessors = predecessors_in_update(after_to_befores, page, update);
+    if predecessors.contains(&page) {
+        let pages = update
+            .iter()
+            .filter(|other| {
+                predecessors.contains(other)
+                    && predecessors_in_update(after_to_befores, **other, update).contains(&page)
+            })
+            .copied()
+            .collect();
+
+        return Err(QueryError::Cycle { pages });
+    }
+
+    Ok(predecessors.len())
+}
+
+/// The middle page of the ordered update, found without ordering it.
+///
+/// That is the page with as many pages of the update before it as after it.
+fn median_page(after_to_befores: &BeforeMap, update: &Update) -> Result<Page, QueryError> {
+    check_odd_length(update)?;
+
+    let predecessors = update
+        .iter()
+        .map(|page| predecessors_in_update(after_to_befores, *page, update))
+        .collect::<Vec<_>>();
+    let cyclic = update
+        .iter()
+        .zip(predecessors.iter())
+        .filter(|(page, befores)| befores.contains(page))
+        .map(|(page, _)| *page)
+        .collect::<Vec<_>>();
+    if !cyclic.is_empty() {
+        return Err(QueryError::Cycle { pages: cyclic });
+    }
+
+    let half = update.len() / 2;
+
+    let mut medians = update.iter().enumerate().filter(|(index, page)| {
+        let successors = predecessors
+            .iter()
+            .filter(|befores| befores.contains(page))
+            .count();
+
+        predecessors[*index].len() == half && successors == half
+    });
+
+    match (medians.next(), medians.next()) {
+        (Some((_, page)), None) => Ok(*page),
+        _ => Err(QueryError::NoMedian),
+    }
+}
+
+fn solution_2(input: Input) -> Result<Page, QueryError> {
     let after_to_befores = before_map(input.0);
 
     input
         .1
         .into_iter()
         .filter(|update| !update_in_order(&after_to_befores, update))
-        .map(|update| get_middle(order_update(&after_to_befores, update)))
+        .map(|update| median_page(&after_to_befores, &update))
         .sum()
 }
 
 fn second() -> Result<(), Box<dyn Error>> {
     let input = read_input(INPUT_PATH).unwrap();
-    let wanted = solution_2(input);
+    let wanted = solution_2(input)?;
     println!("{}", wanted);
     Ok(())
 }
 
+/// A rule broken by an update: `rule.before` sits at `before_index`,
+/// which is behind `rule.after` at `after_index`.
+#[derive(Debug, PartialEq)]
+struct Violation {
+    rule: PageOrdering,
+    before_index: usize,
+    after_index: usize,
+}
+
+fn find_violations(after_to_befores: &BeforeMap, update: &Update) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    for (after_index, page) in update.iter().enumerate() {
+        let Some(befores) = after_to_befores.get(page) else {
+            continue;
+        };
+
+        for (offset, other_page) in update[after_index + 1..].iter().enumerate() {
+            if befores.contains(other_page) {
+                violations.push(Violation {
+                    rule: PageOrdering {
+                        before: *other_page,
+                        after: *page,
+                    },
+                    before_index: after_index + 1 + offset,
+                    after_index,
+                });
+            }
+        }
+    }
+
+    violations
+}
+
+/// Size of a maximum matching from `edges[i]` on the left to indices on the right, by Kuhn's algorithm.
+fn maximum_matching(edges: &[Vec<usize>]) -> usize {
+    fn augment(
+        edges: &[Vec<usize>],
+        left: usize,
+        visited: &mut [bool],
+        matched: &mut [Option<usize>],
+    ) -> bool {
+        for right in edges[left].iter() {
+            if visited[*right] {
+                continue;
+            }
+            visited[*right] = true;
+
+            let free = match matched[*right] {
+                None => true,
+                Some(other) => augment(edges, other, visited, matched),
+            };
+            if free {
+                matched[*right] = Some(left);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    let mut matched = vec![None; edges.len()];
+
+    (0..edges.len())
+        .filter(|left| augment(edges, *left, &mut vec![false; edges.len()], &mut matched))
+        .count()
+}
+
+/// Fewest pages to take out and put back elsewhere to put the update in order.
+///
+/// The pages that stay must not contain two pages the rules require the other way round,
+/// and every such set can be completed to an order. Those conflicts form a partial order,
+/// so by Dilworth's theorem the most pages that can stay are the update's length
+/// minus a maximum matching of conflicts, which leaves exactly that matching to move.
+fn minimum_moves(after_to_befores: &BeforeMap, update: &Update) -> Result<usize, OrderError> {
+    order_update(after_to_befores, update.clone())?;
+
+    // conflicts[i] lists the later indices holding pages that must come before update[i].
+    let conflicts = update
+        .iter()
+        .enumerate()
+        .map(|(index, page)| -> Vec<usize> {
+            let predecessors = predecessors_in_update(after_to_befores, *page, update);
+            (index + 1..update.len())
+                .filter(|later| predecessors.contains(&update[*later]))
+                .collect()
+        })
+        .collect::<Vec<_>>();
+
+    Ok(maximum_matching(&conflicts))
+}
+
+#[derive(Debug, PartialEq)]
+struct Explanation {
+    violations: Vec<Violation>,
+    moves: usize,
+}
+
+fn explain_update(
+    after_to_befores: &BeforeMap,
+    update: &Update,
+) -> Result<Explanation, OrderError> {
+    Ok(Explanation {
+        violations: find_violations(after_to_befores, update),
+        moves: minimum_moves(after_to_befores, update)?,
+    })
+}
+
+fn print_explanations() -> Result<(), Box<dyn Error>> {
+    let (page_orderings, updates) = read_input(INPUT_PATH)?;
+    let after_to_befores = before_map(page_orderings);
+
+    for (index, update) in updates.iter().enumerate() {
+        let Explanation { violations, moves } = match explain_update(&after_to_befores, update) {
+            Ok(explanation) => explanation,
+            Err(error) => {
+                println!("update {}: {:?}, {}", index, update, error);
+                continue;
+            }
+        };
+        if violations.is_empty() {
+            continue;
+        }
+
+        println!("update {}: {:?}, {} moves", index, update, moves);
+        for Violation {
+            rule,
+            before_index,
+            after_index,
+        } in violations.iter()
+        {
+            println!(
+                "  {}|{}: {} at {} is behind {} at {}",
+                rule.before, rule.after, rule.before, before_index, rule.after, after_index
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Rules as a graph from each page to the pages that must come after it.
+type RuleGraph = BTreeMap<Page, BTreeSet<Page>>;
+
+/// Builds the rule graph restricted to `pages`.
+fn rule_graph(page_orderings: &[PageOrdering], pages: &BTreeSet<Page>) -> RuleGraph {
+    let mut graph: RuleGraph = pages.iter().map(|page| (*page, BTreeSet::new())).collect();
+
+    for PageOrdering { before, after } in page_orderings.iter() {
+        if pages.contains(before) && pages.contains(after) {
+            graph.entry(*before).or_default().insert(*after);
+        }
+    }
+
+    graph
+}
+
+fn all_pages(page_orderings: &[PageOrdering]) -> BTreeSet<Page> {
+    page_orderings
+        .iter()
+        .flat_map(|PageOrdering { before, after }| [*before, *after])
+        .collect()
+}
+
+/// Pages in the order their depth first search finishes.
+fn finish_order(graph: &RuleGraph) -> Vec<Page> {
+    let mut visited = HashSet::new();
+    let mut order = Vec::new();
+
+    for start in graph.keys() {
+        if !visited.insert(*start) {
+            continue;
+        }
+
+        let mut stack = Vec::from([(*start, graph[start].iter())]);
+        while let Some((page, afters)) = stack.last_mut() {
+            match afters.find(|after| !visited.contains(*after)) {
+                Some(after) => {
+                    visited.insert(*after);
+                    stack.push((*after, graph[after].iter()));
+                }
+                None => {
+                    order.push(*page);
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    order
+}
+
+/// Strongly connected components of the graph, following Kosaraju.
+///
+/// Each component is sorted, and so is the list of components.
+fn strongly_connected_components(graph: &RuleGraph) -> Vec<Vec<Page>> {
+    let mut transposed: RuleGraph = graph.keys().map(|page| (*page, BTreeSet::new())).collect();
+    for (before, afters) in graph.iter() {
+        for after in afters.iter() {
+            transposed.entry(*after).or_default().insert(*before);
+        }
+    }
+
+    let mut assigned = HashSet::new();
+    let mut components = Vec::new();
+
+    for start in finish_order(graph).into_iter().rev() {
+        if !assigned.insert(start) {
+            continue;
+        }
+
+        let mut component = Vec::new();
+        let mut stack = Vec::from([start]);
+        while let Some(page) = stack.pop() {
+            component.push(page);
+
+            for before in transposed[&page].iter() {
+                if assigned.insert(*before) {
+                    stack.push(*before);
+                }
+            }
+        }
+
+        component.sort();
+        components.push(component);
+    }
+
+    components.sort();
+    components
+}
+
+/// The shortest cycle of rules within `component`, starting from its smallest possible page.
+fn shortest_cycle(graph: &RuleGraph, component: &[Page]) -> Vec<PageOrdering> {
+    let mut shortest: Vec<PageOrdering> = Vec::new();
+
+    for start in component.iter() {
+        // Breadth first search from `start` back to `start`.
+        let mut parents: HashMap<Page, Page> = HashMap::new();
+        let mut queue = VecDeque::from([*start]);
+        let mut closing = None;
+
+        while let Some(page) = queue.pop_front() {
+            let afters = graph[&page]
+                .iter()
+                .filter(|after| component.binary_search(after).is_ok());
+
+            for after in afters {
+                if after == start {
+                    closing = Some(page);
+                    break;
+                }
+                if !parents.contains_key(after) {
+                    parents.insert(*after, page);
+                    queue.push_back(*after);
+                }
+            }
+
+            if closing.is_some() {
+                break;
+            }
+        }
+
+        let Some(mut page) = closing else {
+            continue;
+        };
+
+        let mut cycle = Vec::from([PageOrdering {
+            before: page,
+            after: *start,
+        }]);
+        while page != *start {
+            let parent = parents[&page];
+            cycle.push(PageOrdering {
+                before: parent,
+                after: page,
+            });
+            page = parent;
+        }
+        cycle.reverse();
+
+        if shortest.is_empty() || cycle.len() < shortest.len() {
+            shortest = cycle;
+        }
+    }
+
+    shortest
+}
+
+/// Minimal cycles of rules, one per strongly connected component that has a cycle.
+fn find_cycles(page_orderings: &[PageOrdering], pages: &BTreeSet<Page>) -> Vec<Vec<PageOrdering>> {
+    let graph = rule_graph(page_orderings, pages);
+
+    strongly_connected_components(&graph)
+        .into_iter()
+        .map(|component| shortest_cycle(&graph, &component))
+        .filter(|cycle| !cycle.is_empty())
+        .collect()
+}
+
+/// What is wrong with a set of rules.
+#[derive(Debug, Default, PartialEq)]
+struct RuleReport {
+    duplicates: Vec<PageOrdering>,
+    self_references: Vec<PageOrdering>,
+    /// Minimal cycles among all pages.
+    cycles: Vec<Vec<PageOrdering>>,
+    /// Minimal cycles among the pages of an update, by update index.
+    update_cycles: Vec<(usize, Vec<Vec<PageOrdering>>)>,
+}
+
+fn validate_rules(page_orderings: &[PageOrdering], updates: &[Update]) -> RuleReport {
+    let mut seen = HashSet::new();
+    let mut duplicates = Vec::new();
+    for page_ordering in page_orderings.iter() {
+        if !seen.insert(page_ordering) {
+            duplicates.push(*page_ordering);
+        }
+    }
+
+    let self_references = page_orderings
+        .iter()
+        .filter(|PageOrdering { before, after }| before == after)
+        .copied()
+        .collect();
+
+    let update_cycles = updates
+        .iter()
+        .enumerate()
+        .map(|(index, update)| -> (usize, Vec<Vec<PageOrdering>>) {
+            let pages = update.iter().copied().collect();
+            (index, find_cycles(page_orderings, &pages))
+        })
+        .filter(|(_, cycles)| !cycles.is_empty())
+        .collect();
+
+    RuleReport {
+        duplicates,
+        self_references,
+        cycles: find_cycles(page_orderings, &all_pages(page_orderings)),
+        update_cycles,
+    }
+}
+
+fn format_rules(page_orderings: &[PageOrdering]) -> String {
+    page_orderings
+        .iter()
+        .map(|PageOrdering { before, after }| format!("{}|{}", before, after))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn print_validation() -> Result<(), Box<dyn Error>> {
+    let (page_orderings, updates) = read_input(INPUT_PATH)?;
+    let report = validate_rules(&page_orderings, &updates);
+
+    println!("duplicate rules: {}", format_rules(&report.duplicates));
+    println!(
+        "self referencing rules: {}",
+        format_rules(&report.self_references)
+    );
+    for cycle in report.cycles.iter() {
+        println!("cycle among all pages: {}", format_rules(cycle));
+    }
+    for (index, cycles) in report.update_cycles.iter() {
+        for cycle in cycles.iter() {
+            println!("cycle in update {}: {}", index, format_rules(cycle));
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct DotOptions {
+    /// Drop every rule that is implied by a chain of other rules.
+    transitive_reduction: bool,
+    /// Colour the rules that the update breaks.
+    highlight_violations: bool,
+}
+
+fn reachable(graph: &RuleGraph, from: Page, to: Page) -> bool {
+    let mut visited = HashSet::from([from]);
+    let mut stack = Vec::from([from]);
+
+    while let Some(page) = stack.pop() {
+        for after in graph[&page].iter() {
+            if *after == to {
+                return true;
+            }
+            if visited.insert(*after) {
+                stack.push(*after);
+            }
+        }
+    }
+
+    false
+}
+
+/// Removes rules one by one as long as their pages stay connected through other rules.
+///
+/// This keeps reachability intact even when the rules contain cycles,
+/// where a transitive reduction is not unique.
+fn transitive_reduction(mut graph: RuleGraph, keep: &HashSet<PageOrdering>) -> RuleGraph {
+    let edges = graph
+        .iter()
+        .flat_map(|(before, afters)| afters.iter().map(|after| (*before, *after)))
+        .collect::<Vec<_>>();
+
+    for (before, after) in edges {
+        if keep.contains(&PageOrdering { before, after }) {
+            continue;
+        }
+
+        graph.entry(before).or_default().remove(&after);
+        if !reachable(&graph, before, after) {
+            graph.entry(before).or_default().insert(after);
+        }
+    }
+
+    graph
+}
+
+/// Writes the rules as a Graphviz DOT digraph.
+///
+/// With an `update` only the rules among its pages are written.
+/// Highlighted violations are never dropped by the transitive reduction.
+fn export_dot(
+    page_orderings: &[PageOrdering],
+    update: Option<&Update>,
+    options: &DotOptions,
+) -> String {
+    let pages = match update {
+        Some(update) => update.iter().copied().collect(),
+        None => all_pages(page_orderings),
+    };
+
+    let violated = match (update, options.highlight_violations) {
+        (Some(update), true) => find_violations(&before_map(page_orderings.to_vec()), update)
+            .into_iter()
+            .map(|violation| violation.rule)
+            .collect(),
+        _ => HashSet::new(),
+    };
+
+    let mut graph = rule_graph(page_orderings, &pages);
+    if options.transitive_reduction {
+        graph = transitive_reduction(graph, &violated);
+    }
+
+    let mut lines = Vec::from(["digraph rules {".to_owned()]);
+    for page in pages.iter() {
+        lines.push(format!("  {};", page));
+    }
+    for (before, afters) in graph.iter() {
+        for after in afters.iter() {
+            let rule = PageOrdering {
+                before: *before,
+                after: *after,
+            };
+
+            match violated.contains(&rule) {
+                true => lines.push(format!("  {} -> {} [color=red];", before, after)),
+                false => lines.push(format!("  {} -> {};", before, after)),
+            }
+        }
+    }
+    lines.push("}".to_owned());
+
+    lines.join("\n")
+}
+
+/// Prints DOT for `--dot` or `--dot=<update index>`, reduced with `--reduce`.
+fn print_dot(dot_arg: &str) -> Result<(), Box<dyn Error>> {
+    let (page_orderings, updates) = read_input(INPUT_PATH)?;
+
+    let update = match dot_arg.strip_prefix("--dot=") {
+        Some(index) => Some(
+            updates
+                .get(index.parse::<usize>()?)
+                .ok_or("No update with that index.")?,
+        ),
+        None => None,
+    };
+    let options = DotOptions {
+        transitive_reduction: env::args().any(|arg| arg == "--reduce"),
+        highlight_violations: true,
+    };
+
+    println!("{}", export_dot(&page_orderings, update, &options));
+    Ok(())
+}
+
+/// Rules that can change over time, with the validity of every update kept up to date.
+///
+/// A rule only matters to updates that contain both of its pages,
+/// so adding or removing it only re-checks those.
+struct IncrementalRules {
+    after_to_befores: BeforeMap,
+    /// How often each rule was added, so duplicates can be removed one at a time.
+    rule_counts: HashMap<PageOrdering, usize>,
+    updates: Vec<Update>,
+    page_to_updates: HashMap<Page, HashSet<usize>>,
+    valid: Vec<bool>,
+}
+
+fn incremental_rules(page_orderings: Vec<PageOrdering>, updates: Vec<Update>) -> IncrementalRules {
+    let mut rule_counts = HashMap::new();
+    for page_ordering in page_orderings.iter() {
+        *rule_counts.entry(*page_ordering).or_insert(0) += 1;
+    }
+
+    let mut page_to_updates: HashMap<Page, HashSet<usize>> = HashMap::new();
+    for (index, update) in updates.iter().enumerate() {
+        for page in update.iter() {
+            page_to_updates.entry(*page).or_default().insert(index);
+        }
+    }
+
+    let after_to_befores = before_map(page_orderings);
+    let valid = updates
+        .iter()
+        .map(|update| update_in_order(&after_to_befores, update))
+        .collect();
+
+    IncrementalRules {
+        after_to_befores,
+        rule_counts,
+        updates,
+        page_to_updates,
+        valid,
+    }
+}
+
+/// Re-checks the updates containing both pages of `rule`, returning their indices.
+fn recheck_updates(rules: &mut IncrementalRules, rule: &PageOrdering) -> Vec<usize> {
+    let (Some(befores), Some(afters)) = (
+        rules.page_to_updates.get(&rule.before),
+        rules.page_to_updates.get(&rule.after),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut affected = befores.intersection(afters).copied().collect::<Vec<_>>();
+    affected.sort();
+
+    for index in affected.iter() {
+        rules.valid[*index] = update_in_order(&rules.after_to_befores, &rules.updates[*index]);
+    }
+
+    affected
+}
+
+/// Adds a rule and returns the indices of the updates that had to be re-checked.
+fn add_rule(rules: &mut IncrementalRules, rule: PageOrdering) -> Vec<usize> {
+    let count = rules.rule_counts.entry(rule).or_insert(0);
+    *count += 1;
+    if *count > 1 {
+        return Vec::new();
+    }
+
+    rules
+        .after_to_befores
+        .entry(rule.after)
+        .or_default()
+        .insert(rule.before);
+
+    recheck_updates(rules, &rule)
+}
+
+/// Removes one copy of a rule and returns the indices of the updates that had to be re-checked.
+fn remove_rule(rules: &mut IncrementalRules, rule: PageOrdering) -> Vec<usize> {
+    let Some(count) = rules.rule_counts.get_mut(&rule) else {
+        return Vec::new();
+    };
+
+    *count -= 1;
+    if *count > 0 {
+        return Vec::new();
+    }
+    rules.rule_counts.remove(&rule);
+
+    if let Some(befores) = rules.after_to_befores.get_mut(&rule.after) {
+        befores.remove(&rule.before);
+        if befores.is_empty() {
+            rules.after_to_befores.remove(&rule.after);
+        }
+    }
+
+    recheck_updates(rules, &rule)
+}
+
+fn valid_updates(rules: &IncrementalRules) -> Vec<usize> {
+    (0..rules.updates.len())
+        .filter(|index| rules.valid[*index])
+        .collect()
+}
+
+fn parse_rule(text: &str) -> Result<PageOrdering, Box<dyn Error>> {
+    let [before, after] = text.split('|').collect::<Vec<_>>()[..] else {
+        return Err(format!("Expected a rule like 47|53, got {}.", text).into());
+    };
+
+    Ok(PageOrdering {
+        before: before.trim().parse()?,
+        after: after.trim().parse()?,
+    })
+}
+
+/// Reads rule changes like `+47|53` or `-47|53` from stdin, one per line,
+/// and reports the valid updates after each.
+fn print_incremental() -> Result<(), Box<dyn Error>> {
+    let (page_orderings, updates) = read_input(INPUT_PATH)?;
+    let mut rules = incremental_rules(page_orderings, updates);
+
+    for line in io::stdin().lines() {
+        let line = line?;
+        let rechecked = match line.trim().strip_prefix('-') {
+            Some(rule) => remove_rule(&mut rules, parse_rule(rule)?),
+            None => add_rule(&mut rules, parse_rule(line.trim().trim_start_matches('+'))?),
+        };
+
+        println!(
+            "{} valid updates, {} re-checked",
+            valid_updates(&rules).len(),
+            rechecked.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Answers `--precedes=<a>,<b>` and `--ranks=<update index>`.
+fn print_query(query_arg: &str) -> Result<(), Box<dyn Error>> {
+    let (page_orderings, updates) = read_input(INPUT_PATH)?;
+
+    if let Some(pages) = query_arg.strip_prefix("--precedes=") {
+        let [a, b] = pages
+            .split(',')
+            .map(|page| page.parse::<Page>())
+            .collect::<Result<Vec<_>, _>>()?[..]
+        else {
+            return Err("Expected two pages like --precedes=47,53.".into());
+        };
+
+        let closure = transitive_closure(&page_orderings);
+        println!("{}", precedes(&closure, a, b)?);
+    }
+
+    if let Some(index) = query_arg.strip_prefix("--ranks=") {
+        let update = updates
+            .get(index.parse::<usize>()?)
+            .ok_or("No update with that index.")?;
+        let after_to_befores = before_map(page_orderings);
+
+        for page in update.iter() {
+            println!(
+                "{}: {}",
+                page,
+                rank_in_update(&after_to_befores, *page, update)?
+            );
+        }
+        println!("median: {}", median_page(&after_to_befores, update)?);
+    }
+
+    Ok(())
+}
+
 pub fn main() -> Result<(), Box<dyn Error>> {
+    if env::args().any(|arg| arg == "--validate") {
+        return print_validation();
+    }
+    if env::args().any(|arg| arg == "--explain") {
+        return print_explanations();
+    }
+    if let Some(dot_arg) = env::args().find(|arg| arg.starts_with("--dot")) {
+        return print_dot(&dot_arg);
+    }
+    if env::args().any(|arg| arg == "--incremental") {
+        return print_incremental();
+    }
+    if let Some(query_arg) =
+        env::args().find(|arg| arg.starts_with("--precedes=") || arg.starts_with("--ranks="))
+    {
+        return print_query(&query_arg);
+    }
+
     println!("01-1:");
     first()?;
     println!("01-2:");
@@ -167,7 +1118,15 @@ pub fn main() -> Result<(), Box<dyn Error>> {
 
 #[cfg(test)]
 mod tests {
-    use super::{before_map, filter_ordered_updates, read_input, solution_1, solution_2, Update};
+    use super::{
+        add_rule, all_pages, before_map, explain_update, export_dot, filter_ordered_updates,
+        find_cycles, format_rules, get_middle, incremental_rules, maximum_matching, median_page,
+        minimum_moves, order_update, precedes, predecessors_in_update, rank_in_update, read_input,
+        remove_rule, rule_graph, solution_1, solution_2, strongly_connected_components,
+        transitive_closure, valid_updates, validate_rules, BeforeMap, DotOptions, Explanation,
+        OrderError, Page, PageOrdering, QueryError, RuleReport, Update, Violation,
+    };
+    use crate::random::next_random;
 
     const EXAMPLE_PATH: &str = "./inputs/05/example.txt";
 
@@ -188,7 +1147,7 @@ mod tests {
     #[test]
     fn should_calculate_first_example() {
         let input = read_input(EXAMPLE_PATH).unwrap();
-        let actual = solution_1(input);
+        let actual = solution_1(input).unwrap();
         let expected = 143;
 
         assert_eq!(actual, expected);
@@ -197,9 +1156,479 @@ mod tests {
     #[test]
     fn should_calculate_second_example() {
         let input = read_input(EXAMPLE_PATH).unwrap();
-        let actual = solution_2(input);
+        let actual = solution_2(input).unwrap();
         let expected = 123;
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn should_order_updates_topologically() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let after_to_befores = before_map(input.0);
+
+        let cases = [
+            (
+                Vec::from([75, 97, 47, 61, 53]),
+                Vec::from([97, 75, 47, 61, 53]),
+            ),
+            (Vec::from([61, 13, 29]), Vec::from([61, 29, 13])),
+            (
+                Vec::from([97, 13, 75, 29, 47]),
+                Vec::from([97, 75, 47, 29, 13]),
+            ),
+        ];
+
+        for (update, expected) in cases {
+            assert_eq!(order_update(&after_to_befores, update), Ok(expected));
+        }
+    }
+
+    #[test]
+    fn should_break_ties_by_original_position() {
+        let after_to_befores = before_map(Vec::from([PageOrdering {
+            before: 1,
+            after: 2,
+        }]));
+
+        let actual = order_update(&after_to_befores, Vec::from([5, 2, 4, 1, 3]));
+        let expected = Ok(Vec::from([5, 4, 1, 2, 3]));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn should_report_cycles() {
+        let after_to_befores = before_map(Vec::from([
+            PageOrdering {
+                before: 47,
+                after: 53,
+            },
+            PageOrdering {
+                before: 53,
+                after: 13,
+            },
+            PageOrdering {
+                before: 13,
+                after: 47,
+            },
+        ]));
+
+        let actual = order_update(&after_to_befores, Vec::from([13, 5, 53, 47]));
+        let expected = Err(OrderError::Cycle {
+            pages: Vec::from([13, 53, 47]),
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn cycle_error_leaves_out_pages_behind_the_cycle() {
+        let after_to_befores =
+            before_map(rules(&[(47, 53), (53, 13), (13, 47), (13, 61), (61, 75)]));
+
+        let actual = order_update(&after_to_befores, Vec::from([75, 13, 5, 53, 61, 47]));
+        let expected = Err(OrderError::Cycle {
+            pages: Vec::from([13, 53, 47]),
+        });
+        assert_eq!(actual, expected);
+
+        let self_reference = before_map(rules(&[(5, 5), (5, 7)]));
+        let actual = order_update(&self_reference, Vec::from([7, 5]));
+        assert_eq!(
+            actual,
+            Err(OrderError::Cycle {
+                pages: Vec::from([5])
+            })
+        );
+    }
+
+    fn rules(pairs: &[(i32, i32)]) -> Vec<PageOrdering> {
+        pairs
+            .iter()
+            .map(|(before, after)| PageOrdering {
+                before: *before,
+                after: *after,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_find_strongly_connected_components() {
+        let page_orderings = rules(&[(1, 2), (2, 3), (3, 1), (3, 4), (4, 5), (5, 4), (6, 1)]);
+        let graph = rule_graph(&page_orderings, &all_pages(&page_orderings));
+
+        let actual = strongly_connected_components(&graph);
+        let expected = Vec::from([Vec::from([1, 2, 3]), Vec::from([4, 5]), Vec::from([6])]);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn should_find_minimal_cycles() {
+        let page_orderings = rules(&[(47, 53), (53, 13), (13, 47), (47, 61), (61, 53), (53, 47)]);
+        let pages = all_pages(&page_orderings);
+
+        let actual = find_cycles(&page_orderings, &pages);
+
+        assert_eq!(actual, Vec::from([rules(&[(47, 53), (53, 47)])]));
+    }
+
+    #[test]
+    fn should_validate_rules() {
+        let page_orderings = rules(&[(47, 53), (53, 13), (13, 47), (47, 53), (5, 5), (1, 2)]);
+        let updates: Vec<Update> = Vec::from([Vec::from([1, 2]), Vec::from([13, 47, 53])]);
+
+        let actual = validate_rules(&page_orderings, &updates);
+        let expected = RuleReport {
+            duplicates: rules(&[(47, 53)]),
+            self_references: rules(&[(5, 5)]),
+            cycles: Vec::from([rules(&[(5, 5)]), rules(&[(13, 47), (47, 53), (53, 13)])]),
+            update_cycles: Vec::from([(1, Vec::from([rules(&[(13, 47), (47, 53), (53, 13)])]))]),
+        };
+
+        assert_eq!(actual, expected);
+        assert_eq!(
+            format_rules(&actual.update_cycles[0].1[0]),
+            "13|47, 47|53, 53|13"
+        );
+    }
+
+    #[test]
+    fn should_accept_example_rules() {
+        let (page_orderings, updates) = read_input(EXAMPLE_PATH).unwrap();
+
+        assert_eq!(
+            validate_rules(&page_orderings, &updates),
+            RuleReport::default()
+        );
+    }
+
+    #[test]
+    fn should_find_maximum_matching() {
+        assert_eq!(maximum_matching(&[]), 0);
+        assert_eq!(maximum_matching(&[Vec::from([1]), Vec::new()]), 1);
+        assert_eq!(
+            maximum_matching(&[Vec::from([0, 1]), Vec::from([0]), Vec::new()]),
+            2
+        );
+    }
+
+    /// Fewest moves by trying every set of pages that could stay where they are.
+    fn minimum_moves_brute_force(after_to_befores: &BeforeMap, update: &Update) -> usize {
+        let predecessors = update
+            .iter()
+            .map(|page| predecessors_in_update(after_to_befores, *page, update))
+            .collect::<Vec<_>>();
+
+        (0..1_u32 << update.len())
+            .filter(|kept| {
+                (0..update.len()).all(|i| {
+                    (i + 1..update.len()).all(|j| {
+                        kept & (1 << i) == 0
+                            || kept & (1 << j) == 0
+                            || !predecessors[i].contains(&update[j])
+                    })
+                })
+            })
+            .map(|kept| update.len() - kept.count_ones() as usize)
+            .min()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn should_find_minimum_moves_for_partial_orders() {
+        // Moving 1 to the front is enough, although no total order keeps the other four in place.
+        let after_to_befores = before_map(rules(&[(1, 2), (1, 3), (2, 5), (4, 5)]));
+        let update = Vec::from([3, 4, 2, 5, 1]);
+        assert_eq!(minimum_moves(&after_to_befores, &update).unwrap(), 1);
+
+        let mut state = 3;
+        for _ in 0..300 {
+            let pages = (1..=7).collect::<Vec<Page>>();
+            let mut pairs = Vec::new();
+            for a in pages.iter() {
+                for b in pages.iter().filter(|b| a < *b) {
+                    if next_random(&mut state, 3) == 0 {
+                        pairs.push((*a, *b));
+                    }
+                }
+            }
+            let after_to_befores = before_map(rules(&pairs));
+
+            let mut update = pages.clone();
+            for index in (1..update.len()).rev() {
+                update.swap(index, next_random(&mut state, index as u64 + 1) as usize);
+            }
+
+            assert_eq!(
+                minimum_moves(&after_to_befores, &update).unwrap(),
+                minimum_moves_brute_force(&after_to_befores, &update),
+                "{:?} {:?}",
+                pairs,
+                update
+            );
+        }
+    }
+
+    #[test]
+    fn should_explain_unordered_updates() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let after_to_befores = before_map(input.0);
+
+        let actual = explain_update(&after_to_befores, &Vec::from([75, 97, 47, 61, 53])).unwrap();
+        let expected = Explanation {
+            violations: Vec::from([Violation {
+                rule: PageOrdering {
+                    before: 97,
+                    after: 75,
+                },
+                before_index: 1,
+                after_index: 0,
+            }]),
+            moves: 1,
+        };
+        assert_eq!(actual, expected);
+
+        let actual = explain_update(&after_to_befores, &Vec::from([97, 13, 75, 29, 47])).unwrap();
+        let violated = actual
+            .violations
+            .iter()
+            .map(|violation| (violation.rule.before, violation.rule.after))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            violated,
+            Vec::from([(75, 13), (29, 13), (47, 13), (47, 29)])
+        );
+        assert_eq!(actual.moves, 2);
+    }
+
+    #[test]
+    fn should_not_explain_ordered_updates() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let after_to_befores = before_map(input.0);
+
+        for update in filter_ordered_updates(after_to_befores.clone(), input.1) {
+            let actual = explain_update(&after_to_befores, &update).unwrap();
+
+            assert_eq!(actual.violations, Vec::new());
+            assert_eq!(actual.moves, 0);
+        }
+    }
+
+    #[test]
+    fn should_export_dot() {
+        let page_orderings = rules(&[(1, 2), (2, 3), (1, 3)]);
+
+        let actual = export_dot(&page_orderings, None, &DotOptions::default());
+        let expected = "digraph rules {\n  1;\n  2;\n  3;\n  1 -> 2;\n  1 -> 3;\n  2 -> 3;\n}";
+        assert_eq!(actual, expected);
+
+        let options = DotOptions {
+            transitive_reduction: true,
+            highlight_violations: false,
+        };
+        let actual = export_dot(&page_orderings, None, &options);
+        let expected = "digraph rules {\n  1;\n  2;\n  3;\n  1 -> 2;\n  2 -> 3;\n}";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn should_export_update_subgraph_with_violations() {
+        let page_orderings = rules(&[(1, 2), (2, 3), (1, 3), (3, 4)]);
+        let update = Vec::from([3, 1, 2]);
+        let options = DotOptions {
+            transitive_reduction: true,
+            highlight_violations: true,
+        };
+
+        let actual = export_dot(&page_orderings, Some(&update), &options);
+        let expected = [
+            "digraph rules {",
+            "  1;",
+            "  2;",
+            "  3;",
+            "  1 -> 2;",
+            "  1 -> 3 [color=red];",
+            "  2 -> 3 [color=red];",
+            "}",
+        ]
+        .join("\n");
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn should_keep_reachability_when_reducing_cycles() {
+        let page_orderings = rules(&[(1, 2), (2, 3), (3, 1), (1, 3)]);
+        let options = DotOptions {
+            transitive_reduction: true,
+            highlight_violations: false,
+        };
+
+        let actual = export_dot(&page_orderings, None, &options);
+        let expected = "digraph rules {\n  1;\n  2;\n  3;\n  1 -> 2;\n  2 -> 3;\n  3 -> 1;\n}";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn should_answer_precedes_transitively() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let closure = transitive_closure(&input.0);
+
+        assert_eq!(precedes(&closure, 97, 13), Ok(true));
+        assert_eq!(precedes(&closure, 13, 97), Ok(false));
+
+        let closure = transitive_closure(&rules(&[(1, 2), (2, 3)]));
+        assert_eq!(precedes(&closure, 1, 3), Ok(true));
+        assert_eq!(precedes(&closure, 3, 1), Ok(false));
+        assert_eq!(precedes(&closure, 1, 4), Ok(false));
+        assert_eq!(precedes(&closure, 1, 1), Ok(false));
+    }
+
+    #[test]
+    fn should_reject_precedes_within_cycles() {
+        let closure = transitive_closure(&rules(&[(1, 2), (2, 3), (3, 1), (3, 4), (5, 5)]));
+        let cycle = Err(QueryError::Cycle {
+            pages: Vec::from([1, 2, 3]),
+        });
+
+        assert_eq!(precedes(&closure, 1, 3), cycle);
+        assert_eq!(precedes(&closure, 3, 1), cycle);
+        assert_eq!(precedes(&closure, 2, 4), Ok(true));
+        assert_eq!(precedes(&closure, 4, 2), Ok(false));
+        assert_eq!(
+            precedes(&closure, 5, 5),
+            Err(QueryError::Cycle {
+                pages: Vec::from([5])
+            })
+        );
+    }
+
+    #[test]
+    fn should_rank_pages_in_update() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let after_to_befores = before_map(input.0);
+        let update = Vec::from([97, 13, 75, 29, 47]);
+
+        let actual = update
+            .iter()
+            .map(|page| rank_in_update(&after_to_befores, *page, &update).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(actual, Vec::from([0, 4, 1, 3, 2]));
+        assert_eq!(
+            rank_in_update(&after_to_befores, 61, &update),
+            Err(QueryError::PageNotInUpdate { page: 61 })
+        );
+    }
+
+    #[test]
+    fn should_not_rank_pages_on_cycles() {
+        let after_to_befores = before_map(rules(&[(1, 2), (2, 1), (2, 4), (3, 1)]));
+        let update = Vec::from([4, 2, 3, 1]);
+        let cycle = Err(QueryError::Cycle {
+            pages: Vec::from([2, 1]),
+        });
+
+        assert_eq!(rank_in_update(&after_to_befores, 1, &update), cycle);
+        assert_eq!(rank_in_update(&after_to_befores, 2, &update), cycle);
+        assert_eq!(rank_in_update(&after_to_befores, 3, &update), Ok(0));
+    }
+
+    #[test]
+    fn should_select_median_without_sorting() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let after_to_befores = before_map(input.0);
+
+        for update in input.1 {
+            let expected = get_middle(order_update(&after_to_befores, update.clone()).unwrap());
+
+            assert_eq!(median_page(&after_to_befores, &update), expected);
+        }
+    }
+
+    #[test]
+    fn should_reject_updates_without_middle() {
+        let after_to_befores = before_map(rules(&[(1, 2)]));
+
+        assert_eq!(get_middle(Vec::new()), Err(QueryError::EmptyUpdate));
+        assert_eq!(
+            get_middle(Vec::from([1, 2])),
+            Err(QueryError::EvenLength { length: 2 })
+        );
+        assert_eq!(
+            median_page(&after_to_befores, &Vec::new()),
+            Err(QueryError::EmptyUpdate)
+        );
+        assert_eq!(
+            median_page(&after_to_befores, &Vec::from([1, 2, 3])),
+            Err(QueryError::NoMedian)
+        );
+    }
+
+    #[test]
+    fn should_report_cycles_instead_of_median() {
+        let after_to_befores = before_map(rules(&[(1, 2), (2, 3), (3, 1), (3, 4)]));
+        let update = Vec::from([4, 3, 2, 1, 5]);
+        let cycle = Err(QueryError::Cycle {
+            pages: Vec::from([3, 2, 1]),
+        });
+
+        assert_eq!(median_page(&after_to_befores, &update), cycle);
+        assert_eq!(
+            solution_2((
+                rules(&[(1, 2), (2, 3), (3, 1), (3, 4)]),
+                Vec::from([update])
+            )),
+            cycle
+        );
+    }
+
+    #[test]
+    fn should_track_validity_incrementally() {
+        let (page_orderings, updates) = read_input(EXAMPLE_PATH).unwrap();
+        let mut rules = incremental_rules(Vec::new(), updates.clone());
+
+        assert_eq!(valid_updates(&rules), Vec::from([0, 1, 2, 3, 4, 5]));
+
+        for rule in page_orderings.iter() {
+            add_rule(&mut rules, *rule);
+        }
+        assert_eq!(valid_updates(&rules), Vec::from([0, 1, 2]));
+
+        // Streaming the rules in must agree with checking from scratch.
+        let from_scratch = incremental_rules(page_orderings.clone(), updates);
+        assert_eq!(rules.valid, from_scratch.valid);
+
+        for rule in page_orderings.iter() {
+            remove_rule(&mut rules, *rule);
+        }
+        assert_eq!(valid_updates(&rules), Vec::from([0, 1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn should_only_recheck_affected_updates() {
+        let updates: Vec<Update> =
+            Vec::from([Vec::from([1, 2, 3]), Vec::from([2, 1]), Vec::from([3, 4])]);
+        let [one_two, four_three, five_six]: [PageOrdering; 3] =
+            rules(&[(1, 2), (4, 3), (5, 6)]).try_into().unwrap();
+        let mut incremental = incremental_rules(Vec::new(), updates);
+
+        assert_eq!(add_rule(&mut incremental, one_two), Vec::from([0, 1]));
+        assert_eq!(valid_updates(&incremental), Vec::from([0, 2]));
+
+        assert_eq!(add_rule(&mut incremental, four_three), Vec::from([2]));
+        assert_eq!(valid_updates(&incremental), Vec::from([0]));
+
+        assert_eq!(add_rule(&mut incremental, five_six), Vec::<usize>::new());
+
+        // Duplicates need to be removed as often as they were added.
+        assert_eq!(add_rule(&mut incremental, one_two), Vec::<usize>::new());
+        assert_eq!(remove_rule(&mut incremental, one_two), Vec::<usize>::new());
+        assert_eq!(valid_updates(&incremental), Vec::from([0]));
+        assert_eq!(remove_rule(&mut incremental, one_two), Vec::from([0, 1]));
+        assert_eq!(valid_updates(&incremental), Vec::from([0, 1]));
+    }
 }
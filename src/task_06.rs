@@ -1,11 +1,20 @@
-use std::{collections::HashMap, error::Error, fs};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    error::Error,
+    fmt, fs,
+    io::{self, Write},
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
 
 const INPUT_PATH: &str = "./inputs/06/input.txt";
 
 type N = i32;
 type Coordinate = (N, N);
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 enum Direction {
     Up,
     Down,
@@ -26,38 +35,111 @@ fn add_coordinates(a: &Coordinate, b: &Coordinate) -> Coordinate {
     (a.0 + b.0, a.1 + b.1)
 }
 
+#[derive(Clone)]
 enum Field {
     Floor,
     Wall,
     Player { direction: Direction },
-    Visited { walked_off: Direction },
 }
 
 type Input = HashMap<Coordinate, Field>;
 
+/// Why a map couldn't be read.
+#[derive(Debug, PartialEq)]
+enum MapError {
+    NoGuard,
+    MultipleGuards {
+        positions: Vec<Coordinate>,
+    },
+    UnknownCharacter {
+        character: char,
+        position: Coordinate,
+    },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::NoGuard => write!(f, "The map has no guard."),
+            MapError::MultipleGuards { positions } => {
+                write!(
+                    f,
+                    "The map has {} guards at {:?}.",
+                    positions.len(),
+                    positions
+                )
+            }
+            MapError::UnknownCharacter {
+                character,
+                position,
+            } => write!(f, "Unknown character {:?} at {:?}.", character, position),
+        }
+    }
+}
+
+impl Error for MapError {}
+
+fn guard_direction(glyph: char) -> Option<Direction> {
+    match glyph {
+        '^' => Some(Direction::Up),
+        '>' => Some(Direction::Right),
+        'v' => Some(Direction::Down),
+        '<' => Some(Direction::Left),
+        _ => None,
+    }
+}
+
+fn guard_glyph(direction: &Direction) -> char {
+    match direction {
+        Direction::Up => '^',
+        Direction::Right => '>',
+        Direction::Down => 'v',
+        Direction::Left => '<',
+    }
+}
+
+fn parse_field(character: char, position: Coordinate) -> Result<Field, MapError> {
+    match character {
+        '.' => Ok(Field::Floor),
+        '#' => Ok(Field::Wall),
+        glyph => match guard_direction(glyph) {
+            Some(direction) => Ok(Field::Player { direction }),
+            None => Err(MapError::UnknownCharacter {
+                character,
+                position,
+            }),
+        },
+    }
+}
+
+fn parse_map(input: &str) -> Result<Input, MapError> {
+    let mut map = Input::new();
+    let mut guards = Vec::new();
+
+    for (y, line) in input.lines().enumerate() {
+        for (x, character) in line.chars().enumerate() {
+            let position = (x as N, y as N);
+            let field = parse_field(character, position)?;
+
+            if let Field::Player { direction: _ } = field {
+                guards.push(position);
+            }
+
+            map.insert(position, field);
+        }
+    }
+
+    match guards.len() {
+        0 => Err(MapError::NoGuard),
+        1 => Ok(map),
+        _ => Err(MapError::MultipleGuards { positions: guards }),
+    }
+}
+
 fn read_input(path: &str) -> Result<Input, Box<dyn Error>> {
     let input = fs::read_to_string(path)?;
 
-    Ok(input
-        .lines()
-        .enumerate()
-        .flat_map(|(y, line)| -> Vec<(Coordinate, Field)> {
-            line.chars()
-                .enumerate()
-                .map(|(x, field)| -> (Coordinate, Field) {
-                    let field = match field {
-                        '#' => Field::Wall,
-                        '^' => Field::Player {
-                            direction: Direction::Up,
-                        },
-                        _ => Field::Floor,
-                    };
-
-                    ((x as N, y as N), field)
-                })
-                .collect()
-        })
-        .collect())
+    Ok(parse_map(&input)?)
 }
 
 fn find_guard(input: &Input) -> Option<Coordinate> {
@@ -82,53 +164,411 @@ fn rotate_right(direction: &Direction) -> Direction {
     }
 }
 
-fn walk_guard(mut input: Input) -> Input {
-    let mut turn_log = Vec::new();
+fn rotate_left(direction: &Direction) -> Direction {
+    match direction {
+        Direction::Up => Direction::Left,
+        Direction::Left => Direction::Down,
+        Direction::Down => Direction::Right,
+        Direction::Right => Direction::Up,
+    }
+}
 
-    let Some(mut position) = find_guard(&input) else {
-        return input;
+fn flip_direction(direction: &Direction) -> Direction {
+    match direction {
+        Direction::Up => Direction::Down,
+        Direction::Down => Direction::Up,
+        Direction::Left => Direction::Right,
+        Direction::Right => Direction::Left,
+    }
+}
+
+/// How the guard changes heading in front of an obstacle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TurnRule {
+    Right,
+    Left,
+    /// Right on the first obstacle, left on the second, and so on.
+    Alternate,
+    /// Back the way the guard came.
+    Bounce,
+}
+
+/// The rules the guard follows while walking.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct MovementPolicy {
+    turn: TurnRule,
+    /// Headings point diagonally, with `Up` meaning up and to the right.
+    diagonal: bool,
+    /// Leaving the map on one side enters it again on the opposite side.
+    wrap: bool,
+}
+
+/// The movement rules from the puzzle.
+const PUZZLE_POLICY: MovementPolicy = MovementPolicy {
+    turn: TurnRule::Right,
+    diagonal: false,
+    wrap: false,
+};
+
+fn policy_delta(policy: &MovementPolicy, direction: &Direction) -> Coordinate {
+    let delta = direction_to_delta(direction);
+
+    match policy.diagonal {
+        true => add_coordinates(&delta, &direction_to_delta(&rotate_right(direction))),
+        false => delta,
+    }
+}
+
+fn policy_turn(policy: &MovementPolicy, direction: &Direction, turns_taken: usize) -> Direction {
+    match policy.turn {
+        TurnRule::Right => rotate_right(direction),
+        TurnRule::Left => rotate_left(direction),
+        TurnRule::Alternate if turns_taken.is_multiple_of(2) => rotate_right(direction),
+        TurnRule::Alternate => rotate_left(direction),
+        TurnRule::Bounce => flip_direction(direction),
+    }
+}
+
+/// The position the guard steps onto, `None` when it leaves the map.
+///
+/// `wrap_bounds` holds the map's width and height when the policy wraps.
+fn policy_step(
+    policy: &MovementPolicy,
+    input: &Input,
+    wrap_bounds: Option<Coordinate>,
+    position: &Coordinate,
+    direction: &Direction,
+) -> Option<Coordinate> {
+    let (x, y) = add_coordinates(position, &policy_delta(policy, direction));
+
+    match (input.contains_key(&(x, y)), wrap_bounds) {
+        (true, _) => Some((x, y)),
+        (false, Some((width, height))) => Some((x.rem_euclid(width), y.rem_euclid(height)))
+            .filter(|position| input.contains_key(position)),
+        (false, None) => None,
+    }
+}
+
+/// The guard changing heading on the spot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Turn {
+    position: Coordinate,
+    from: Direction,
+    to: Direction,
+}
+
+/// Everything the guard does until leaving the map or walking in a loop.
+#[derive(Debug, PartialEq)]
+struct Trace {
+    /// Every position and heading the guard takes, in order, including turns on the spot.
+    steps: Vec<(Coordinate, Direction)>,
+    turns: Vec<Turn>,
+    /// The last position on the map and the heading the guard leaves with, `None` for loops.
+    exit: Option<(Coordinate, Direction)>,
+}
+
+fn trace_guard(input: &Input) -> Trace {
+    trace_guard_with(input, &PUZZLE_POLICY)
+}
+
+fn trace_guard_with(input: &Input, policy: &MovementPolicy) -> Trace {
+    let mut trace = Trace {
+        steps: Vec::new(),
+        turns: Vec::new(),
+        exit: None,
     };
 
-    while let Some(Field::Player { direction }) = input.get(&position) {
-        let mut next_position = add_coordinates(&position, &direction_to_delta(direction));
-        let mut next_direction = *direction;
+    let Some(mut position) = find_guard(input) else {
+        return trace;
+    };
+    let Some(Field::Player { mut direction }) = input.get(&position) else {
+        return trace;
+    };
 
-        if let Some(Field::Wall) = input.get(&next_position) {
-            next_direction = rotate_right(direction);
-            next_position = add_coordinates(&position, &direction_to_delta(&next_direction));
-            turn_log.push(position);
-        }
+    let wrap_bounds = policy.wrap.then(|| {
+        let width = input.keys().map(|(x, _)| x + 1).max().unwrap_or(0);
+        let height = input.keys().map(|(_, y)| y + 1).max().unwrap_or(0);
+        (width, height)
+    });
 
-        input.insert(
-            position,
-            Field::Visited {
-                walked_off: next_direction,
-            },
-        );
-        if input.contains_key(&next_position) {
-            input.insert(
-                next_position,
-                Field::Player {
-                    direction: next_direction,
-                },
-            );
+    // Alternating turns make the next turn part of the guard's state.
+    let turn_phase = |turns: &Vec<Turn>| match policy.turn {
+        TurnRule::Alternate => turns.len() % 2,
+        _ => 0,
+    };
+
+    let mut seen = HashSet::new();
+
+    while seen.insert((position, direction, turn_phase(&trace.turns))) {
+        trace.steps.push((position, direction));
+
+        let Some(next_position) = policy_step(policy, input, wrap_bounds, &position, &direction)
+        else {
+            trace.exit = Some((position, direction));
+            break;
+        };
+        match input.get(&next_position) {
+            Some(Field::Wall) => {
+                let next_direction = policy_turn(policy, &direction, trace.turns.len());
+                trace.turns.push(Turn {
+                    position,
+                    from: direction,
+                    to: next_direction,
+                });
+                direction = next_direction;
+            }
+            _ => position = next_position,
         }
-        position = next_position;
     }
 
-    return input;
+    trace
+}
+
+fn visited_positions(trace: &Trace) -> HashSet<Coordinate> {
+    trace.steps.iter().map(|(position, _)| *position).collect()
 }
 
 fn solution_1(input: Input) -> N {
-    let walked_input = walk_guard(input);
+    visited_positions(&trace_guard(&input)).len() as N
+}
+
+/// Sorted obstacle positions per row and column, so a straight run is one lookup.
+struct JumpTable {
+    /// The x coordinates of the walls in each row.
+    rows: Vec<Vec<N>>,
+    /// The y coordinates of the walls in each column.
+    columns: Vec<Vec<N>>,
+}
+
+fn jump_table(input: &Input) -> JumpTable {
+    let width = input.keys().map(|(x, _)| x + 1).max().unwrap_or(0);
+    let height = input.keys().map(|(_, y)| y + 1).max().unwrap_or(0);
+
+    let mut rows = vec![Vec::new(); height as usize];
+    let mut columns = vec![Vec::new(); width as usize];
+
+    for ((x, y), field) in input {
+        if let Field::Wall = field {
+            rows[*y as usize].push(*x);
+            columns[*x as usize].push(*y);
+        }
+    }
+
+    rows.iter_mut().for_each(|row| row.sort_unstable());
+    columns.iter_mut().for_each(|column| column.sort_unstable());
+
+    JumpTable { rows, columns }
+}
+
+/// The closest entry of `line` after `from` when walking forwards, or before it otherwise.
+fn closest_on_line(line: &[N], from: N, forwards: bool) -> Option<N> {
+    if forwards {
+        line.get(line.partition_point(|&w| w <= from)).copied()
+    } else {
+        line.partition_point(|&w| w < from)
+            .checked_sub(1)
+            .map(|index| line[index])
+    }
+}
+
+/// The first obstacle ahead of the guard, taking one extra obstacle into account.
+fn next_obstacle(
+    table: &JumpTable,
+    position: Coordinate,
+    direction: Direction,
+    extra: Option<Coordinate>,
+) -> Option<Coordinate> {
+    let (x, y) = position;
+    let (dx, dy) = direction_to_delta(&direction);
+
+    let (line, from, forwards) = match direction {
+        Direction::Up | Direction::Down => (table.columns.get(x as usize)?, y, dy > 0),
+        Direction::Left | Direction::Right => (table.rows.get(y as usize)?, x, dx > 0),
+    };
+
+    let wall = closest_on_line(line, from, forwards);
+    let extra = extra
+        .filter(|(ex, ey)| if dx == 0 { *ex == x } else { *ey == y })
+        .map(|(ex, ey)| if dx == 0 { ey } else { ex })
+        .filter(|e| if forwards { *e > from } else { *e < from });
+
+    let closest = match (wall, extra) {
+        (Some(w), Some(e)) => Some(if forwards { w.min(e) } else { w.max(e) }),
+        (w, e) => w.or(e),
+    }?;
+
+    Some(if dx == 0 { (x, closest) } else { (closest, y) })
+}
+
+/// Walks the guard one straight run at a time and tells whether it ends up in a loop.
+fn guard_loops(
+    table: &JumpTable,
+    mut position: Coordinate,
+    mut direction: Direction,
+    extra: Option<Coordinate>,
+) -> bool {
+    let mut seen = HashSet::new();
+
+    while let Some(obstacle) = next_obstacle(table, position, direction, extra) {
+        let (dx, dy) = direction_to_delta(&direction);
+        position = (obstacle.0 - dx, obstacle.1 - dy);
+        direction = rotate_right(&direction);
+
+        if !seen.insert((position, direction)) {
+            return true;
+        }
+    }
+
+    false
+}
+
+fn guard_start(input: &Input) -> Option<(Coordinate, Direction)> {
+    let position = find_guard(input)?;
+    match input.get(&position) {
+        Some(Field::Player { direction }) => Some((position, *direction)),
+        _ => None,
+    }
+}
 
-    walked_input
+/// Positions the guard visits besides its start, in a fixed order.
+fn obstruction_candidates(input: &Input, start: Coordinate) -> Vec<Coordinate> {
+    let mut candidates = visited_positions(&trace_guard(input))
         .into_iter()
-        .filter(|(_, field)| match field {
-            Field::Visited { walked_off: _ } => true,
-            _ => false,
-        })
-        .count() as N
+        .filter(|position| *position != start)
+        .collect::<Vec<_>>();
+    candidates.sort_unstable();
+    candidates
+}
+
+/// Keeps the items for which `keep` holds, split into one chunk per thread.
+///
+/// Every worker gets its own state from `init`, and the results keep the order of `items`.
+fn parallel_filter<T, S>(
+    items: &[T],
+    threads: usize,
+    init: impl Fn() -> S + Sync,
+    keep: impl Fn(&mut S, &T) -> bool + Sync,
+) -> Vec<T>
+where
+    T: Copy + Send + Sync,
+{
+    if items.is_empty() {
+        return Vec::new();
+    }
+
+    let chunk_size = items.len().div_ceil(threads.max(1));
+
+    thread::scope(|scope| {
+        let workers = items
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let (init, keep) = (&init, &keep);
+                scope.spawn(move || {
+                    let mut state = init();
+                    chunk
+                        .iter()
+                        .filter(|item| keep(&mut state, item))
+                        .copied()
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    })
+}
+
+/// Positions on the guard's path where a single extra obstacle makes it loop.
+fn find_obstructions(input: &Input, threads: usize) -> Vec<Coordinate> {
+    let Some((start, direction)) = guard_start(input) else {
+        return Vec::new();
+    };
+
+    let table = jump_table(input);
+    let candidates = obstruction_candidates(input, start);
+
+    parallel_filter(
+        &candidates,
+        threads,
+        || (),
+        |_, candidate| guard_loops(&table, start, direction, Some(*candidate)),
+    )
+}
+
+/// Like `find_obstructions`, but walking cell by cell on each worker's own copy of the map.
+fn find_obstructions_walking(input: &Input, threads: usize) -> Vec<Coordinate> {
+    let Some((start, _)) = guard_start(input) else {
+        return Vec::new();
+    };
+
+    let candidates = obstruction_candidates(input, start);
+
+    parallel_filter(
+        &candidates,
+        threads,
+        || input.clone(),
+        |map, candidate| {
+            let previous = map.insert(*candidate, Field::Wall);
+            let loops = trace_guard(map).exit.is_none();
+            if let Some(previous) = previous {
+                map.insert(*candidate, previous);
+            }
+            loops
+        },
+    )
+}
+
+fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// The thread count from `--threads=<n>`, all available threads without it.
+fn threads_arg() -> Result<usize, Box<dyn Error>> {
+    match env::args().find_map(|arg| arg.strip_prefix("--threads=").map(String::from)) {
+        Some(threads) => Ok(threads.parse::<usize>()?.max(1)),
+        None => Ok(default_threads()),
+    }
+}
+
+fn solution_2(input: Input, threads: usize) -> N {
+    find_obstructions(&input, threads).len() as N
+}
+
+fn time<T>(label: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    println!("{}: {:?}", label, start.elapsed());
+    result
+}
+
+/// Times the obstruction search on 1, 2, 4, … threads up to `--threads=<n>`.
+fn bench() -> Result<(), Box<dyn Error>> {
+    let input = read_input(INPUT_PATH)?;
+    let max_threads = threads_arg()?;
+
+    let thread_counts = (0..)
+        .map(|power| 1 << power)
+        .take_while(|threads| *threads < max_threads)
+        .chain([max_threads])
+        .collect::<Vec<usize>>();
+
+    let expected = find_obstructions(&input, 1);
+    for threads in thread_counts {
+        let jumping = time(&format!("jump table, {} threads", threads), || {
+            find_obstructions(&input, threads)
+        });
+        let walking = time(&format!("cell walking, {} threads", threads), || {
+            find_obstructions_walking(&input, threads)
+        });
+        assert_eq!(jumping, expected);
+        assert_eq!(walking, expected);
+    }
+    println!("{} obstructions", expected.len());
+
+    Ok(())
 }
 
 fn first() -> Result<(), Box<dyn Error>> {
@@ -138,69 +578,189 @@ fn first() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn flip_direction(direction: &Direction) -> Direction {
-    match direction {
-        Direction::Up => Direction::Down,
-        Direction::Down => Direction::Up,
-        Direction::Left => Direction::Right,
-        Direction::Right => Direction::Left,
+fn second(threads: usize) -> Result<(), Box<dyn Error>> {
+    let input = read_input(INPUT_PATH)?;
+    let wanted = solution_2(input, threads);
+    println!("{}", wanted);
+    Ok(())
+}
+
+/// The trail mark for walking in `direction`, `+` where it crosses a different one.
+fn trail_mark(previous: Option<char>, direction: &Direction) -> char {
+    let mark = match direction {
+        Direction::Up | Direction::Down => '|',
+        Direction::Left | Direction::Right => '-',
+    };
+
+    match previous {
+        Some(previous) if previous != mark => '+',
+        _ => mark,
     }
 }
 
-fn foo(input: Input) -> Vec<Coordinate> {
-    let mut visited = input
-        .into_iter()
-        .filter_map(|(position, field)| -> Option<(Coordinate, Direction)> {
-            match field {
-                Field::Floor => None,
-                Field::Wall => None,
-                Field::Player { direction: _ } => None,
-                Field::Visited { walked_off } => Some((position, walked_off)),
-            }
+fn render_frame(
+    input: &Input,
+    trail: &HashMap<Coordinate, char>,
+    guard: &(Coordinate, Direction),
+) -> String {
+    let width = input.keys().map(|(x, _)| x + 1).max().unwrap_or(0);
+    let height = input.keys().map(|(_, y)| y + 1).max().unwrap_or(0);
+
+    (0..height)
+        .map(|y| -> String {
+            (0..width)
+                .map(|x| {
+                    if (x, y) == guard.0 {
+                        return guard_glyph(&guard.1);
+                    }
+
+                    match (trail.get(&(x, y)), input.get(&(x, y))) {
+                        (Some(mark), _) => *mark,
+                        (None, Some(Field::Wall)) => '#',
+                        (None, Some(_)) => '.',
+                        (None, None) => ' ',
+                    }
+                })
+                .collect()
         })
-        .collect::<HashMap<Coordinate, Direction>>();
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-    let mut dragons = Vec::new();
+/// Calls `show` with every frame of the patrol, one per step of the trace.
+fn for_each_frame(
+    input: &Input,
+    trace: &Trace,
+    mut show: impl FnMut(usize, String) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut trail = HashMap::new();
 
-    while !visited.is_empty() {
-        let Some(position) = visited.keys().next() else {
-            break;
-        };
-        let position = *position;
+    for (index, step) in trace.steps.iter().enumerate() {
+        show(index, render_frame(input, &trail, step))?;
 
-        let Some(direction) = visited.remove(&position) else {
-            break;
-        };
-        let delta = direction_to_delta(&direction);
+        let (position, direction) = step;
+        let mark = trail_mark(trail.get(position).copied(), direction);
+        trail.insert(*position, mark);
+    }
+
+    Ok(())
+}
+
+struct PlaybackOptions {
+    delay: Duration,
+    /// Writes the frames as text files into this directory instead of animating them.
+    frames_dir: Option<String>,
+}
+
+fn play_patrol(input: &Input, options: &PlaybackOptions) -> Result<(), Box<dyn Error>> {
+    let trace = trace_guard(input);
+
+    if let Some(frames_dir) = &options.frames_dir {
+        fs::create_dir_all(frames_dir)?;
+
+        return for_each_frame(input, &trace, |index, frame| {
+            let path = Path::new(frames_dir).join(format!("frame_{:05}.txt", index));
+            Ok(fs::write(path, frame)?)
+        });
+    }
+
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "\x1b[2J\x1b[?25l")?;
+
+    for_each_frame(input, &trace, |_, frame| {
+        write!(stdout, "\x1b[H{}", frame)?;
+        stdout.flush()?;
+        thread::sleep(options.delay);
+        Ok(())
+    })?;
+
+    writeln!(stdout, "\x1b[?25h")?;
+    Ok(())
+}
+
+/// Animates the patrol for `--visualize`, at `--fps=<n>` frames per second
+/// or into text files with `--frames=<dir>`.
+fn visualize() -> Result<(), Box<dyn Error>> {
+    let input = read_input(INPUT_PATH)?;
 
-        // Remove visited in same direction:
-        let mut next_position = add_coordinates(&position, &delta);
-        while visited.contains_key(&next_position) {
-            visited.remove(&next_position);
-            next_position = add_coordinates(&next_position, &delta);
+    let fps = match env::args().find_map(|arg| arg.strip_prefix("--fps=").map(String::from)) {
+        Some(fps) => fps.parse::<u64>()?.max(1),
+        None => 30,
+    };
+    let options = PlaybackOptions {
+        delay: Duration::from_millis(1000 / fps),
+        frames_dir: env::args().find_map(|arg| arg.strip_prefix("--frames=").map(String::from)),
+    };
+
+    play_patrol(&input, &options)
+}
+
+/// Reads a policy like `left`, `bounce,diagonal` or `alternate,wrap`.
+fn parse_policy(text: &str) -> Result<MovementPolicy, Box<dyn Error>> {
+    let mut policy = PUZZLE_POLICY;
+
+    for part in text.split(',') {
+        match part {
+            "right" => policy.turn = TurnRule::Right,
+            "left" => policy.turn = TurnRule::Left,
+            "alternate" => policy.turn = TurnRule::Alternate,
+            "bounce" => policy.turn = TurnRule::Bounce,
+            "diagonal" => policy.diagonal = true,
+            "wrap" => policy.wrap = true,
+            _ => return Err(format!("Unknown movement rule {:?}.", part).into()),
         }
     }
 
-    dragons
+    Ok(policy)
 }
 
-fn second() -> Result<(), Box<dyn Error>> {
+/// Walks the guard with `--policy=<rules>` and tells how the patrol ends.
+fn print_policy(policy_arg: &str) -> Result<(), Box<dyn Error>> {
     let input = read_input(INPUT_PATH)?;
-    let wanted = 0;
-    println!("{}", wanted);
+    let policy = parse_policy(policy_arg)?;
+    let trace = trace_guard_with(&input, &policy);
+
+    println!(
+        "{} positions, {} turns",
+        visited_positions(&trace).len(),
+        trace.turns.len()
+    );
+    match trace.exit {
+        Some((position, direction)) => println!("leaves at {:?} heading {:?}", position, direction),
+        None => println!("loops"),
+    }
+
     Ok(())
 }
 
 pub fn main() -> Result<(), Box<dyn Error>> {
+    if env::args().any(|arg| arg == "--visualize") {
+        return visualize();
+    }
+    if env::args().any(|arg| arg == "--bench") {
+        return bench();
+    }
+    if let Some(policy_arg) =
+        env::args().find_map(|arg| arg.strip_prefix("--policy=").map(String::from))
+    {
+        return print_policy(&policy_arg);
+    }
+
     println!("01-1:");
     first()?;
     println!("01-2:");
-    second()
+    second(threads_arg()?)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{read_input, solution_1, walk_guard};
+    use super::{
+        find_obstructions, find_obstructions_walking, for_each_frame, guard_loops, guard_start,
+        jump_table, next_obstacle, parallel_filter, parse_map, parse_policy, play_patrol,
+        read_input, solution_1, solution_2, trace_guard, trace_guard_with, Direction, Field,
+        MapError, MovementPolicy, PlaybackOptions, Turn, TurnRule, PUZZLE_POLICY,
+    };
+    use std::{env, fs, process, time::Duration};
 
     const EXAMPLE_PATH: &str = "./inputs/06/example.txt";
 
@@ -213,37 +773,377 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
-    // #[test]
-    // fn should_suspect_the_right_coordinate() {
-    //     let actual = suspect_coordinate(&((4, 1), (8, 1), (8, 6)));
-    //     let expected = (4, 6);
+    #[test]
+    fn should_trace_example() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let trace = trace_guard(&input);
+
+        assert_eq!(trace.steps.first(), Some(&((4, 6), Direction::Up)));
+        assert_eq!(trace.steps.len(), 55);
+        assert_eq!(
+            trace.turns.first(),
+            Some(&Turn {
+                position: (4, 1),
+                from: Direction::Up,
+                to: Direction::Right,
+            })
+        );
+        assert_eq!(trace.turns.len(), 10);
+        assert_eq!(trace.exit, Some(((7, 9), Direction::Down)));
+    }
+
+    #[test]
+    fn should_parse_all_guard_glyphs() {
+        for (glyph, direction) in [
+            ('^', Direction::Up),
+            ('>', Direction::Right),
+            ('v', Direction::Down),
+            ('<', Direction::Left),
+        ] {
+            let map = parse_map(&format!("#.\n.{}", glyph)).unwrap();
+
+            assert!(matches!(map.get(&(0, 0)), Some(Field::Wall)));
+            assert!(matches!(map.get(&(1, 0)), Some(Field::Floor)));
+            assert!(matches!(
+                map.get(&(1, 1)),
+                Some(Field::Player { direction: d }) if *d == direction
+            ));
+        }
+    }
+
+    #[test]
+    fn should_walk_from_any_heading() {
+        let map = parse_map("...\n.<.\n...").unwrap();
+        let trace = trace_guard(&map);
+
+        assert_eq!(trace.exit, Some(((0, 1), Direction::Left)));
+        assert_eq!(trace.steps.len(), 2);
+    }
+
+    #[test]
+    fn should_reject_malformed_maps() {
+        assert_eq!(parse_map("..\n.#").err(), Some(MapError::NoGuard));
+        assert_eq!(
+            parse_map("^.\n.v").err(),
+            Some(MapError::MultipleGuards {
+                positions: Vec::from([(0, 0), (1, 1)])
+            })
+        );
+        assert_eq!(
+            parse_map("..\n^x").err(),
+            Some(MapError::UnknownCharacter {
+                character: 'x',
+                position: (1, 1)
+            })
+        );
+    }
+
+    #[test]
+    fn should_find_next_obstacle() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let table = jump_table(&input);
+
+        assert_eq!((table.rows.len(), table.columns.len()), (10, 10));
+        assert_eq!(
+            next_obstacle(&table, (4, 6), Direction::Up, None),
+            Some((4, 0))
+        );
+        assert_eq!(
+            next_obstacle(&table, (4, 1), Direction::Right, None),
+            Some((9, 1))
+        );
+        assert_eq!(next_obstacle(&table, (7, 7), Direction::Down, None), None);
+        assert_eq!(
+            next_obstacle(&table, (4, 6), Direction::Up, Some((4, 3))),
+            Some((4, 3))
+        );
+        assert_eq!(
+            next_obstacle(&table, (4, 6), Direction::Up, Some((4, 8))),
+            Some((4, 0))
+        );
+    }
+
+    #[test]
+    fn should_jump_out_of_example() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let table = jump_table(&input);
+        let (start, direction) = guard_start(&input).unwrap();
+
+        assert!(!guard_loops(&table, start, direction, None));
+        assert!(guard_loops(&table, start, direction, Some((3, 6))));
+    }
+
+    #[test]
+    fn should_find_obstructions() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let actual = find_obstructions(&input, 1);
+        let expected = Vec::from([(1, 8), (3, 6), (3, 8), (6, 7), (7, 7), (7, 9)]);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn should_agree_with_walking_cell_by_cell() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let (start, _) = guard_start(&input).unwrap();
+
+        let mut expected = input
+            .keys()
+            .filter(|position| **position != start)
+            .filter(|position| {
+                let mut obstructed = input.clone();
+                obstructed.insert(**position, Field::Wall);
+                trace_guard(&obstructed).exit.is_none()
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        expected.sort_unstable();
+
+        assert_eq!(find_obstructions(&input, 1), expected);
+        assert_eq!(find_obstructions_walking(&input, 1), expected);
+    }
+
+    #[test]
+    fn should_search_deterministically_in_parallel() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let expected = find_obstructions(&input, 1);
+
+        for threads in [0, 2, 3, 8, 100] {
+            assert_eq!(find_obstructions(&input, threads), expected);
+            assert_eq!(find_obstructions_walking(&input, threads), expected);
+        }
+    }
+
+    #[test]
+    fn should_filter_in_order() {
+        let items = (0..100).collect::<Vec<u32>>();
+        let actual = parallel_filter(
+            &items,
+            7,
+            || 0,
+            |seen, item| {
+                *seen += 1;
+                item % 3 == 0
+            },
+        );
+        let expected = (0..100).filter(|item| item % 3 == 0).collect::<Vec<_>>();
+
+        assert_eq!(actual, expected);
+        assert!(parallel_filter(&[] as &[u32], 4, || (), |_, _| true).is_empty());
+    }
+
+    #[test]
+    fn should_compute_example_2() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let expected = 6;
+
+        assert_eq!(solution_2(input.clone(), 1), expected);
+        assert_eq!(solution_2(input, 3), expected);
+    }
+
+    #[test]
+    fn should_render_patrol_frames() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let trace = trace_guard(&input);
+
+        let mut frames = Vec::new();
+        for_each_frame(&input, &trace, |_, frame| {
+            frames.push(frame);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(frames.len(), trace.steps.len());
+        assert_eq!(
+            frames[0],
+            fs::read_to_string(EXAMPLE_PATH).unwrap().trim_end()
+        );
+        assert_eq!(
+            frames[7],
+            [
+                "....#.....",
+                "....+>...#",
+                "....|.....",
+                "..#.|.....",
+                "....|..#..",
+                "....|.....",
+                ".#..|.....",
+                "........#.",
+                "#.........",
+                "......#...",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn should_dump_frames_headless() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let frames_dir = env::temp_dir().join(format!("aoc-06-frames-{}", process::id()));
+        let options = PlaybackOptions {
+            delay: Duration::ZERO,
+            frames_dir: Some(frames_dir.to_string_lossy().into_owned()),
+        };
+
+        play_patrol(&input, &options).unwrap();
+
+        let last = fs::read_to_string(frames_dir.join("frame_00054.txt")).unwrap();
+        let files = fs::read_dir(&frames_dir).unwrap().count();
+        fs::remove_dir_all(&frames_dir).unwrap();
+
+        assert_eq!(files, 55);
+        assert_eq!(last.lines().nth(9), Some("......#v.."));
+        assert_eq!(last.lines().nth(1), Some("....+---+#"));
+    }
+
+    fn all_policies() -> Vec<MovementPolicy> {
+        let mut policies = Vec::new();
+        for turn in [
+            TurnRule::Right,
+            TurnRule::Left,
+            TurnRule::Alternate,
+            TurnRule::Bounce,
+        ] {
+            for diagonal in [false, true] {
+                for wrap in [false, true] {
+                    policies.push(MovementPolicy {
+                        turn,
+                        diagonal,
+                        wrap,
+                    });
+                }
+            }
+        }
+        policies
+    }
+
+    #[test]
+    fn should_follow_the_puzzle_policy_by_default() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+
+        assert_eq!(
+            trace_guard_with(&input, &PUZZLE_POLICY),
+            trace_guard(&input)
+        );
+    }
+
+    #[test]
+    fn should_turn_left_and_bounce() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+
+        let left = trace_guard_with(
+            &input,
+            &MovementPolicy {
+                turn: TurnRule::Left,
+                ..PUZZLE_POLICY
+            },
+        );
+        assert_eq!(
+            left.turns.first(),
+            Some(&Turn {
+                position: (4, 1),
+                from: Direction::Up,
+                to: Direction::Left,
+            })
+        );
+        assert_eq!(left.exit, Some(((0, 1), Direction::Left)));
+
+        let bounce = trace_guard_with(
+            &input,
+            &MovementPolicy {
+                turn: TurnRule::Bounce,
+                ..PUZZLE_POLICY
+            },
+        );
+        assert_eq!(bounce.turns.len(), 1);
+        assert_eq!(bounce.exit, Some(((4, 9), Direction::Down)));
+    }
+
+    #[test]
+    fn should_walk_diagonally() {
+        let input = parse_map("....\n....\n.^..\n....").unwrap();
+        let trace = trace_guard_with(
+            &input,
+            &MovementPolicy {
+                diagonal: true,
+                ..PUZZLE_POLICY
+            },
+        );
+
+        assert_eq!(
+            trace.steps,
+            Vec::from([
+                ((1, 2), Direction::Up),
+                ((2, 1), Direction::Up),
+                ((3, 0), Direction::Up)
+            ])
+        );
+        assert_eq!(trace.exit, Some(((3, 0), Direction::Up)));
+    }
+
+    #[test]
+    fn should_detect_loops_when_wrapping() {
+        let input = parse_map("...\n.^.\n...").unwrap();
+        let trace = trace_guard_with(
+            &input,
+            &MovementPolicy {
+                wrap: true,
+                ..PUZZLE_POLICY
+            },
+        );
 
-    //     assert_eq!(actual, expected);
-    // }
+        assert_eq!(trace.exit, None);
+        assert_eq!(
+            trace.steps,
+            Vec::from([
+                ((1, 1), Direction::Up),
+                ((1, 0), Direction::Up),
+                ((1, 2), Direction::Up)
+            ])
+        );
+    }
 
-    // #[test]
-    // fn should_find_loops() {
-    //     let input = read_input(EXAMPLE_PATH).unwrap();
-    //     let (walked_input, turn_log) = walk_guard(input);
-    //     let triplets = into_triplets(turn_log);
+    #[test]
+    fn should_parse_policies() {
+        assert_eq!(parse_policy("right").unwrap(), PUZZLE_POLICY);
+        assert_eq!(
+            parse_policy("bounce,diagonal,wrap").unwrap(),
+            MovementPolicy {
+                turn: TurnRule::Bounce,
+                diagonal: true,
+                wrap: true,
+            }
+        );
+        assert!(parse_policy("sideways").is_err());
+    }
 
-    //     let actual = triplets
-    //         .into_iter()
-    //         .filter(|triplet| can_loop(&walked_input, triplet))
-    //         .map(|triplet| suspect_coordinate(&triplet))
-    //         .collect::<Vec<_>>();
+    #[test]
+    fn should_detect_loops_when_boxed_in() {
+        let input = parse_map("###\n#^#\n###").unwrap();
 
-    //     let expected = Vec::from([(4, 6), (6, 6), (2, 8), (6, 7), /*(4, 8),*/ (7, 8)]);
+        for policy in all_policies() {
+            let trace = trace_guard_with(&input, &policy);
 
-    //     assert_eq!(actual, expected);
-    // }
+            assert_eq!(trace.exit, None, "{:?}", policy);
+            assert!(trace.steps.len() <= 8, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn should_terminate_or_loop_with_every_policy() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let states = input.len() * 4 * 2;
 
-    // #[test]
-    // fn should_compute_example_2() {
-    //     let input = read_input(EXAMPLE_PATH).unwrap();
-    //     let actual = solution_2(input);
-    //     let expected = 6;
+        for policy in all_policies() {
+            let trace = trace_guard_with(&input, &policy);
 
-    //     assert_eq!(actual, expected);
-    // }
+            assert!(trace.steps.len() <= states, "{:?}", policy);
+            if policy.wrap {
+                assert_eq!(trace.exit, None, "{:?}", policy);
+            }
+            if let Some(exit) = trace.exit {
+                assert_eq!(trace.steps.last(), Some(&exit), "{:?}", policy);
+            }
+        }
+    }
 }
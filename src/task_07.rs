@@ -1,4 +1,4 @@
-use std::{cmp::max, error::Error, fs};
+use std::{env, error::Error, fs};
 
 const INPUT_PATH: &str = "./inputs/07/input.txt";
 
@@ -33,67 +33,247 @@ fn read_input(path: &str) -> Result<Input, Box<dyn Error>> {
         .collect())
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 enum Operator {
     Add,
     Multiply,
+    Concatenate,
 }
 
-fn next_operator(operators: N) -> (Operator, N) {
-    let operator = if (operators & 0x1) == 0 {
-        Operator::Add
-    } else {
-        Operator::Multiply
-    };
-    let next_operators = operators >> 1;
+const ADD_MULTIPLY: [Operator; 2] = [Operator::Add, Operator::Multiply];
+const WITH_CONCATENATION: [Operator; 3] =
+    [Operator::Add, Operator::Multiply, Operator::Concatenate];
+
+/// The smallest power of ten above `n`, so that `a * shift(b) + b` concatenates.
+fn shift(n: N) -> N {
+    let mut power = 10;
+    while power <= n {
+        power *= 10;
+    }
+    power
+}
+
+#[cfg(test)]
+fn next_operator(operators: N, choices: &[Operator]) -> (Operator, N) {
+    let base = choices.len() as N;
+    let operator = choices[(operators % base) as usize];
+    let next_operators = operators / base;
 
     (operator, next_operators)
 }
 
-fn calculate_equation(equation: &Equation, mut operators: N) -> N {
+#[cfg(test)]
+fn calculate_equation(equation: &Equation, mut operators: N, choices: &[Operator]) -> N {
     let mut inputs = equation.inputs.to_owned();
     inputs.reverse();
 
     let mut value = inputs.pop().unwrap();
 
     while let Some(input) = inputs.pop() {
-        let (operator, next_operators) = next_operator(operators);
+        let (operator, next_operators) = next_operator(operators, choices);
         operators = next_operators;
 
         match operator {
             Operator::Add => value += input,
             Operator::Multiply => value *= input,
+            Operator::Concatenate => value = value * shift(input) + input,
         }
     }
 
     value
 }
 
-fn operator_choices(equation: &Equation) -> N {
+#[cfg(test)]
+fn operator_choices(equation: &Equation, choices: &[Operator]) -> N {
     let power: u32 = (equation.inputs.len() - 1).try_into().unwrap();
-    max(2_i64.pow(power), 0)
+    (choices.len() as N).pow(power)
+}
+
+/// Tries every combination of operators.
+#[cfg(test)]
+fn equation_solvable_brute_force(equation: &Equation, choices: &[Operator]) -> bool {
+    (0..operator_choices(equation, choices))
+        .any(|operators| calculate_equation(equation, operators, choices) == equation.value)
 }
 
-fn equation_solvable(equation: &Equation) -> bool {
-    for operators in 0..operator_choices(equation) {
-        let value = calculate_equation(equation, operators);
+/// What the value before the last input had to be.
+#[derive(Debug, PartialEq)]
+enum Previous {
+    Value(N),
+    /// Multiplying by zero reaches zero from any value.
+    Any,
+}
 
-        if equation.value == value {
-            return true;
+/// Undoes `operator` applied to `last`, `None` where it can't produce `target`.
+///
+/// Inputs are never negative, so adding or concatenating can only be undone
+/// while the target is at least the last input.
+fn undo(target: N, last: N, operator: &Operator) -> Option<Previous> {
+    match operator {
+        Operator::Add => (target >= last).then(|| Previous::Value(target - last)),
+        Operator::Multiply => match last {
+            0 => (target == 0).then_some(Previous::Any),
+            last => (target % last == 0).then(|| Previous::Value(target / last)),
+        },
+        Operator::Concatenate => {
+            let shift = shift(last);
+            (target >= last && (target - last) % shift == 0)
+                .then(|| Previous::Value((target - last) / shift))
         }
     }
+}
+
+/// Calls `found` with every operator sequence for `inputs` after `prefix`, until it returns `true`.
+fn every_sequence(
+    inputs: usize,
+    choices: &[Operator],
+    prefix: &mut Vec<Operator>,
+    found: &mut dyn FnMut(&[Operator]) -> bool,
+) -> bool {
+    if inputs <= 1 {
+        return found(prefix);
+    }
 
-    false
+    choices.iter().any(|operator| {
+        prefix.push(*operator);
+        let stop = every_sequence(inputs - 1, choices, prefix, found);
+        prefix.pop();
+        stop
+    })
 }
 
-fn solution_1(input: Input) -> N {
+/// Works backwards from `target` and calls `found` with every operator sequence
+/// that reaches it, until `found` returns `true`.
+///
+/// `suffix` holds the operators already undone, last one first.
+fn search(
+    target: N,
+    inputs: &[N],
+    choices: &[Operator],
+    suffix: &mut Vec<Operator>,
+    found: &mut dyn FnMut(&[Operator]) -> bool,
+) -> bool {
+    let Some((last, rest)) = inputs.split_last() else {
+        return false;
+    };
+
+    if rest.is_empty() {
+        if target != *last {
+            return false;
+        }
+        let operators = suffix.iter().rev().copied().collect::<Vec<_>>();
+        return found(&operators);
+    }
+
+    choices.iter().any(|operator| {
+        let Some(previous) = undo(target, *last, operator) else {
+            return false;
+        };
+
+        suffix.push(*operator);
+        let stop = match previous {
+            Previous::Value(previous) => search(previous, rest, choices, suffix, found),
+            Previous::Any => {
+                let tail = suffix.iter().rev().copied().collect::<Vec<_>>();
+                every_sequence(rest.len(), choices, &mut Vec::new(), &mut |head| {
+                    found(&[head, &tail[..]].concat())
+                })
+            }
+        };
+        suffix.pop();
+        stop
+    })
+}
+
+fn equation_solvable(equation: &Equation, choices: &[Operator]) -> bool {
+    search(
+        equation.value,
+        &equation.inputs,
+        choices,
+        &mut Vec::new(),
+        &mut |_| true,
+    )
+}
+
+/// The first operator sequence found that solves the equation.
+fn find_witness(equation: &Equation, choices: &[Operator]) -> Option<Vec<Operator>> {
+    let mut witness = None;
+    search(
+        equation.value,
+        &equation.inputs,
+        choices,
+        &mut Vec::new(),
+        &mut |operators| {
+            witness = Some(operators.to_vec());
+            true
+        },
+    );
+    witness
+}
+
+/// How many distinct operator sequences solve the equation.
+fn count_solutions(equation: &Equation, choices: &[Operator]) -> usize {
+    let mut count = 0;
+    search(
+        equation.value,
+        &equation.inputs,
+        choices,
+        &mut Vec::new(),
+        &mut |_| {
+            count += 1;
+            false
+        },
+    );
+    count
+}
+
+fn operator_symbol(operator: &Operator) -> &'static str {
+    match operator {
+        Operator::Add => "+",
+        Operator::Multiply => "*",
+        Operator::Concatenate => "||",
+    }
+}
+
+/// Renders a solution like `81 * 40 + 27 = 3267`.
+fn render_solution(equation: &Equation, operators: &[Operator]) -> String {
+    let mut parts = Vec::from([equation.inputs[0].to_string()]);
+
+    for (operator, input) in operators.iter().zip(&equation.inputs[1..]) {
+        parts.push(operator_symbol(operator).to_string());
+        parts.push(input.to_string());
+    }
+
+    format!("{} = {}", parts.join(" "), equation.value)
+}
+
+/// Every solvable equation together with the first operator sequence that solves it.
+fn solve_equations<'a>(
+    input: &'a Input,
+    choices: &[Operator],
+) -> Vec<(&'a Equation, Vec<Operator>)> {
+    input
+        .iter()
+        .filter_map(|equation| Some((equation, find_witness(equation, choices)?)))
+        .collect()
+}
+
+fn calibration_result(input: Input, choices: &[Operator]) -> N {
     input
         .into_iter()
-        .filter(|equation| equation_solvable(equation))
+        .filter(|equation| equation_solvable(equation, choices))
         .map(|equation| equation.value)
         .sum::<N>()
 }
 
+fn solution_1(input: Input) -> N {
+    calibration_result(input, &ADD_MULTIPLY)
+}
+
+fn solution_2(input: Input) -> N {
+    calibration_result(input, &WITH_CONCATENATION)
+}
+
 fn first() -> Result<(), Box<dyn Error>> {
     let input = read_input(INPUT_PATH)?;
     let wanted = solution_1(input);
@@ -102,12 +282,41 @@ fn first() -> Result<(), Box<dyn Error>> {
 }
 
 fn second() -> Result<(), Box<dyn Error>> {
-    let wanted = 0;
+    let input = read_input(INPUT_PATH)?;
+    let wanted = solution_2(input);
     println!("{}", wanted);
     Ok(())
 }
 
+/// Prints a solution for every solvable equation for `--witness`,
+/// allowing concatenation with `--concatenate` and counting all solutions with `--count`.
+fn print_witnesses() -> Result<(), Box<dyn Error>> {
+    let input = read_input(INPUT_PATH)?;
+    let choices = match env::args().any(|arg| arg == "--concatenate") {
+        true => &WITH_CONCATENATION[..],
+        false => &ADD_MULTIPLY[..],
+    };
+    let count = env::args().any(|arg| arg == "--count");
+
+    for (equation, operators) in solve_equations(&input, choices) {
+        match count {
+            true => println!(
+                "{} ({} in total)",
+                render_solution(equation, &operators),
+                count_solutions(equation, choices)
+            ),
+            false => println!("{}", render_solution(equation, &operators)),
+        }
+    }
+
+    Ok(())
+}
+
 pub fn main() -> Result<(), Box<dyn Error>> {
+    if env::args().any(|arg| arg == "--witness") {
+        return print_witnesses();
+    }
+
     println!("01-1:");
     first()?;
     println!("01-2:");
@@ -116,16 +325,26 @@ pub fn main() -> Result<(), Box<dyn Error>> {
 
 #[cfg(test)]
 mod tests {
-    use super::{calculate_equation, next_operator, read_input, solution_1, Equation, Operator};
+    use super::{
+        calculate_equation, count_solutions, equation_solvable, equation_solvable_brute_force,
+        find_witness, next_operator, operator_choices, read_input, render_solution, shift,
+        solution_1, solution_2, solve_equations, Equation, Operator, ADD_MULTIPLY, N,
+        WITH_CONCATENATION,
+    };
+    use crate::random::next_random;
 
     const EXAMPLE_PATH: &str = "./inputs/07/example.txt";
 
     #[test]
     fn next_operator_behaves() {
-        assert_eq!(next_operator(0), (Operator::Add, 0));
-        assert_eq!(next_operator(1), (Operator::Multiply, 0));
-        assert_eq!(next_operator(2), (Operator::Add, 1));
-        assert_eq!(next_operator(3), (Operator::Multiply, 1));
+        assert_eq!(next_operator(0, &ADD_MULTIPLY), (Operator::Add, 0));
+        assert_eq!(next_operator(1, &ADD_MULTIPLY), (Operator::Multiply, 0));
+        assert_eq!(next_operator(2, &ADD_MULTIPLY), (Operator::Add, 1));
+        assert_eq!(next_operator(3, &ADD_MULTIPLY), (Operator::Multiply, 1));
+        assert_eq!(
+            next_operator(5, &WITH_CONCATENATION),
+            (Operator::Concatenate, 1)
+        );
     }
 
     #[test]
@@ -134,10 +353,26 @@ mod tests {
             value: 3267,
             inputs: Vec::from([81, 40, 27]),
         };
-        let actual = calculate_equation(&equation, 2);
+        let actual = calculate_equation(&equation, 2, &ADD_MULTIPLY);
         assert_eq!(actual, equation.value)
     }
 
+    #[test]
+    fn concatenates_inputs() {
+        assert_eq!(shift(0), 10);
+        assert_eq!(shift(9), 10);
+        assert_eq!(shift(10), 100);
+
+        let equation = Equation {
+            value: 7290,
+            inputs: Vec::from([6, 8, 6, 15]),
+        };
+        // 6 * 8 || 6 * 15, one base three digit per operator.
+        let operators = 1 + 2 * 3 + 9;
+        let actual = calculate_equation(&equation, operators, &WITH_CONCATENATION);
+        assert_eq!(actual, equation.value);
+    }
+
     #[test]
     fn should_compute_example_1() {
         let input = read_input(EXAMPLE_PATH).unwrap();
@@ -146,4 +381,131 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn should_compute_example_2() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+        let actual = solution_2(input);
+        let expected = 11387;
+
+        assert_eq!(actual, expected);
+    }
+
+    fn generate_equations(count: usize, seed: u64) -> Vec<Equation> {
+        let mut state = seed;
+        let mut next = |bound: u64| next_random(&mut state, bound) as N;
+
+        (0..count)
+            .map(|_| {
+                let length = 1 + next(6) as usize;
+                let inputs = (0..length).map(|_| next(25)).collect::<Vec<_>>();
+                let value = match next(2) {
+                    0 => {
+                        let operators = next(3_u64.pow(length as u32 - 1));
+                        let equation = Equation {
+                            value: 0,
+                            inputs: inputs.clone(),
+                        };
+                        calculate_equation(&equation, operators, &WITH_CONCATENATION)
+                    }
+                    _ => next(100_000),
+                };
+
+                Equation { value, inputs }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn agrees_with_brute_force() {
+        for equation in generate_equations(2_000, 7) {
+            for choices in [&ADD_MULTIPLY[..], &WITH_CONCATENATION[..]] {
+                assert_eq!(
+                    equation_solvable(&equation, choices),
+                    equation_solvable_brute_force(&equation, choices),
+                    "{:?} with {:?}",
+                    equation,
+                    choices
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn should_find_witnesses() {
+        let input = read_input(EXAMPLE_PATH).unwrap();
+
+        let actual = solve_equations(&input, &ADD_MULTIPLY)
+            .into_iter()
+            .map(|(equation, operators)| render_solution(equation, &operators))
+            .collect::<Vec<_>>();
+        let expected = Vec::from([
+            "10 * 19 = 190",
+            "81 * 40 + 27 = 3267",
+            "11 + 6 * 16 + 20 = 292",
+        ]);
+        assert_eq!(actual, expected);
+
+        let with_concatenation = solve_equations(&input, &WITH_CONCATENATION);
+        assert_eq!(with_concatenation.len(), 6);
+        assert_eq!(
+            render_solution(with_concatenation[3].0, &with_concatenation[3].1),
+            "6 * 8 || 6 * 15 = 7290"
+        );
+    }
+
+    #[test]
+    fn should_count_solutions() {
+        let equation = Equation {
+            value: 3267,
+            inputs: Vec::from([81, 40, 27]),
+        };
+        assert_eq!(count_solutions(&equation, &ADD_MULTIPLY), 2);
+
+        let zeros = Equation {
+            value: 0,
+            inputs: Vec::from([5, 0, 0]),
+        };
+        assert_eq!(count_solutions(&zeros, &ADD_MULTIPLY), 3);
+        assert_eq!(
+            find_witness(&zeros, &ADD_MULTIPLY),
+            Some(Vec::from([Operator::Multiply, Operator::Add]))
+        );
+    }
+
+    #[test]
+    fn witnesses_agree_with_brute_force() {
+        for equation in generate_equations(2_000, 11) {
+            for choices in [&ADD_MULTIPLY[..], &WITH_CONCATENATION[..]] {
+                let expected = (0..operator_choices(&equation, choices))
+                    .filter(|operators| {
+                        calculate_equation(&equation, *operators, choices) == equation.value
+                    })
+                    .count();
+
+                assert_eq!(
+                    count_solutions(&equation, choices),
+                    expected,
+                    "{:?}",
+                    equation
+                );
+                if let Some(witness) = find_witness(&equation, choices) {
+                    let operators = witness
+                        .iter()
+                        .rev()
+                        .map(|operator| {
+                            choices
+                                .iter()
+                                .position(|choice| choice == operator)
+                                .unwrap() as N
+                        })
+                        .fold(0, |operators, digit| operators * choices.len() as N + digit);
+                    assert_eq!(
+                        calculate_equation(&equation, operators, choices),
+                        equation.value
+                    );
+                }
+            }
+        }
+    }
 }